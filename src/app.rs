@@ -1,10 +1,77 @@
 use egui::vec2;
-use egui_dock::{Tree, DockArea, Style};
-use crate::{Stl,Aff, parsers::{Parser, skl::Skl}};
+use egui_dock::{Tree, DockArea, Style, Node, NodeIndex, TabIndex};
+use crate::{casc, raw, index, layout, job::Job, hex::HexView, parsers::{self, Parser, Reveal, aspect::Aspects}, query::Query, results::Results};
 
 pub struct AppContext {
     tabs: Tree<FileTab>,
-    search: String
+    search: String,
+    regex: bool,
+    case_sensitive: bool,
+    query: Query,
+    query_error: Option<String>,
+    /// bumped whenever the query changes so the tabs know to count their matches again
+    search_version: u64,
+    results: Results,
+    /// id given to the next data tab
+    next_tab: usize,
+    /// folders being parsed, and finished ones until they are dismissed
+    jobs: Vec<Job>,
+    extensions: Vec<String>,
+    /// why `layouts.toml` could not be read, its extensions are missing until it can
+    layout_error: Option<String>
+}
+
+impl AppContext {
+    /// parses the search bar again, an invalid query keeps the last valid one
+    fn update_query(&mut self) {
+        match Query::parse(&self.search, self.regex, self.case_sensitive) {
+            Ok(query) => {
+                self.query = query;
+                self.query_error = None;
+            },
+            Err(error) => self.query_error = Some(error)
+        }
+        self.search_version += 1;
+    }
+
+    fn add_tab(&mut self, parser: Box<dyn Parser>) {
+        self.tabs.push_to_focused_leaf(FileTab::Data(DataTab {
+            id: self.next_tab,
+            parser,
+            matches: None,
+            reveal: None
+        }));
+        self.next_tab += 1;
+    }
+
+    /// focuses the data tab with the id and has it open and scroll to the search result
+    fn reveal(&mut self, tab: usize, reveal: Reveal) {
+        let mut reveal = Some(reveal);
+        let mut found = None;
+        for (node_index, node) in self.tabs.iter_mut().enumerate() {
+            if let Node::Leaf { tabs, .. } = node {
+                for (tab_index, x) in tabs.iter_mut().enumerate() {
+                    if let FileTab::Data(data) = x {
+                        if data.id == tab {
+                            data.reveal = reveal.take();
+                            found = Some((NodeIndex(node_index), TabIndex(tab_index)));
+                        }
+                    }
+                }
+            }
+        }
+        if let Some((node, tab)) = found {
+            self.tabs.set_focused_node(node);
+            self.tabs.set_active_tab(node, tab);
+        }
+    }
+
+    fn clear_search(&mut self) {
+        if !self.search.is_empty() {
+            self.search = Default::default();
+            self.update_query();
+        }
+    }
 }
 
 pub struct App {
@@ -15,7 +82,24 @@ impl Default for App {
     fn default() -> Self {
         let tabs = Tree::new(Vec::new());
         let search = Default::default();
-        let data = AppContext { tabs, search };
+        let (extensions, layout_error) = match parsers::all_extensions() {
+            Ok(extensions) => (extensions, None),
+            Err(e) => (parsers::EXTENSIONS.iter().map(|x| x.to_string()).collect(), Some(e.to_string()))
+        };
+        let data = AppContext {
+            tabs,
+            search,
+            regex: false,
+            case_sensitive: false,
+            query: Query::default(),
+            query_error: None,
+            search_version: 0,
+            results: Results::default(),
+            next_tab: 0,
+            jobs: Vec::new(),
+            extensions,
+            layout_error
+        };
 
         Self { 
             data: Box::new(data) 
@@ -36,27 +120,55 @@ impl eframe::App for App {
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
                 ui.menu_button("File", |ui| {
-                    if ui.button("Parse stl folder...").clicked() {
+                    let mut started = None;
+                    let mut layout_error = None;
+                    for extension in &self.data.extensions {
+                        if ui.button(format!("Parse {} folder...", extension)).clicked() {
+                            ui.close_menu();
+                            if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                                let path = path.display().to_string();
+                                match parsers::from_extension(extension) {
+                                    Ok(Some(parser)) => started = Some(Job::parse(format!("Parsing {}", path), parser, path)),
+                                    Ok(None) => (),
+                                    Err(e) => layout_error = Some(e.to_string())
+                                }
+                            }
+                        }
+                    }
+                    if layout_error.is_some() {
+                        self.data.layout_error = layout_error;
+                    }
+                    if ui.button("Parse game folder...").clicked() {
                         ui.close_menu();
                         if let Some(path) = rfd::FileDialog::new().pick_folder() {
                             let path = path.display().to_string();
-                            let _stl = Stl::new().run(path).unwrap();
+                            started = Some(Job::spawn(format!("Extracting {} to casc", path), move || casc::run(&path, "casc")));
                         }
                     }
-                    if ui.button("Parse aff folder...").clicked() {
+                    if ui.button("Extract aspects from aff folder...").clicked() {
                         ui.close_menu();
                         if let Some(path) = rfd::FileDialog::new().pick_folder() {
                             let path = path.display().to_string();
-                            let _aff = Aff::new().run(path).unwrap();
+                            started = Some(Job::parse(format!("Extracting aspects from {}", path), Box::new(Aspects::new()), path));
                         }
                     }
-                    if ui.button("Parse skl folder...").clicked() {
+                    self.data.jobs.extend(started);
+                    if ui.button("Open file in hex viewer...").clicked() {
                         ui.close_menu();
-                        if let Some(path) = rfd::FileDialog::new().pick_folder() {
-                            let path = path.display().to_string();
-                            let _skl = Skl::new().run(path).unwrap();
+                        if let Some(path) = rfd::FileDialog::new().pick_file() {
+                            if let Ok(hex) = HexView::open(&path) {
+                                self.data.tabs.push_to_focused_leaf(FileTab::Hex(hex));
+                            }
                         }
                     }
+                    let mut keep_raw = raw::enabled();
+                    if ui.checkbox(&mut keep_raw, "Keep unknown bytes").changed() {
+                        raw::set_enabled(keep_raw);
+                    }
+                    let mut save_indexes = index::persist();
+                    if ui.checkbox(&mut save_indexes, "Save search indexes").changed() {
+                        index::set_persist(save_indexes);
+                    }
                     if ui.button("Quit").clicked() {
                         _frame.close();
                     }
@@ -65,14 +177,27 @@ impl eframe::App for App {
                 if self.data.tabs.num_tabs() > 0 {
                     ui.allocate_space(vec2(110f32, 1f32));
                     if ui.button("clear").clicked() {
-                        self.data.search = Default::default();
+                        self.data.clear_search();
+                    }
+                    ui.toggle_value(&mut self.data.results.open, "results").on_hover_text("List the hits in every loaded dataset");
+                    let case_toggle = ui.toggle_value(&mut self.data.case_sensitive, "Aa").on_hover_text("Match case");
+                    let regex_toggle = ui.toggle_value(&mut self.data.regex, ".*").on_hover_text("Use regular expressions");
+                    let mut text = egui::TextEdit::singleline(&mut self.data.search)
+                        .hint_text("Search in json tabs, file: key: value: hash: limit a term, \"quotes\" keep phrases together")
+                        .desired_width(f32::INFINITY);
+                    if self.data.query_error.is_some() {
+                        text = text.text_color(ui.visuals().error_fg_color);
+                    }
+                    let mut response = ui.add(text);
+                    if let Some(error) = &self.data.query_error {
+                        response = response.on_hover_text(error);
+                    }
+                    if response.changed() || case_toggle.changed() || regex_toggle.changed() {
+                        self.data.update_query();
                     }
-                    ui.add(egui::TextEdit::singleline(&mut self.data.search)
-                        .hint_text("Search in json tabs")
-                        .desired_width(f32::INFINITY));
                 }
                 else {
-                    self.data.search = Default::default();
+                    self.data.clear_search();
                 }
             });
         });
@@ -84,34 +209,87 @@ impl eframe::App for App {
             .show(ctx, |ui| {
                 ui.heading("Data Viewers");
                 ui.separator();
-                if ui.button("Load stl data").clicked() {
-                    if let Some(parser) = Stl::new().load_data_file() {
-                        let tab = FileTab::new(parser);
-                        self.data.tabs.push_to_focused_leaf(tab);
-                        self.data.search = Default::default();                    
-                    }
+                if let Some(error) = &self.data.layout_error {
+                    ui.colored_label(ui.visuals().error_fg_color, format!("{}: {}", layout::LAYOUTS_FILE, error));
+                    ui.separator();
                 }
-                if ui.button("Load aff data").clicked() {
-                    if let Some(parser) = Aff::new().load_data_file() {
-                        let tab = FileTab::new(parser);
-                        self.data.tabs.push_to_focused_leaf(tab);
-                        self.data.search = Default::default();                    
+                let mut loaded = None;
+                let mut layout_error = None;
+                for extension in &self.data.extensions {
+                    if ui.button(format!("Load {} data", extension)).clicked() {
+                        match parsers::from_extension(extension) {
+                            Ok(parser) => loaded = parser.and_then(|x| x.load_data_file()),
+                            Err(e) => layout_error = Some(e.to_string())
+                        }
                     }
                 }
-                if ui.button("Load skl data").clicked() {
-                    if let Some(parser) = Skl::new().load_data_file() {
-                        let tab = FileTab::new(parser);
-                        self.data.tabs.push_to_focused_leaf(tab);
-                        self.data.search = Default::default();                    
+                if layout_error.is_some() {
+                    self.data.layout_error = layout_error;
+                }
+                if let Some(parser) = loaded {
+                    self.data.add_tab(parser);
+                    self.data.clear_search();
+                }
+                if ui.button("Load aspect data").clicked() {
+                    if let Some(parser) = Aspects::new().load_data_file() {
+                        self.data.add_tab(parser);
+                        self.data.clear_search();
                     }
                 }
         });
 
+        let mut parsed = Vec::new();
+        for job in self.data.jobs.iter_mut() {
+            parsed.extend(job.take());
+        }
+        for parser in parsed {
+            self.data.add_tab(parser);
+            self.data.clear_search();
+        }
+        if self.data.jobs.iter().any(|x| x.is_running()) {
+            ctx.request_repaint();
+        }
+        if !self.data.jobs.is_empty() {
+            egui::TopBottomPanel::bottom("jobs_panel").show(ctx, |ui| {
+                let mut dismissed = None;
+                for (i, job) in self.data.jobs.iter().enumerate() {
+                    if ui.push_id(i, |ui| job.show(ui)).inner {
+                        dismissed = Some(i);
+                    }
+                }
+                if let Some(i) = dismissed {
+                    self.data.jobs.remove(i);
+                }
+            });
+        }
+
+        if self.data.results.open && self.data.tabs.num_tabs() > 0 {
+            let AppContext { tabs, query, search_version, results, .. } = self.data.as_mut();
+            let datasets = tabs.tabs()
+                .filter_map(|x| match x {
+                    FileTab::Data(data) => Some((data.id, data.parser.as_ref())),
+                    FileTab::Hex(_) => None
+                })
+                .collect();
+            results.update(query, *search_version, datasets);
+
+            let clicked = egui::TopBottomPanel::bottom("results_panel")
+                .resizable(true)
+                .default_height(200f32)
+                .show(ctx, |ui| results.show(ui, query).map(|x| (x.tab, x.reveal())))
+                .inner;
+            if let Some((tab, reveal)) = clicked {
+                self.data.reveal(tab, reveal);
+            }
+        }
+
         if self.data.tabs.num_tabs() > 0 {
-            DockArea::new(&mut self.data.tabs)
+            let AppContext { tabs, query, search_version, .. } = self.data.as_mut();
+            DockArea::new(tabs)
                 .style(Style::from_egui(ctx.style().as_ref()))
                 .show(ctx, &mut FileViewer {
-                    filter: self.data.search.clone()
+                    query,
+                    version: *search_version
                 });            
         }
         else {
@@ -124,30 +302,50 @@ impl eframe::App for App {
     }
 }
 
-struct FileTab {
-    parser: Box<dyn Parser>,
+enum FileTab {
+    Data(DataTab),
+    Hex(HexView)
 }
 
-impl FileTab {
-    fn new(parser: Box<dyn Parser>) -> Self {
-        Self {
-            parser
-        }
-    }
+struct DataTab {
+    id: usize,
+    parser: Box<dyn Parser>,
+    /// the search version the match count was made for, with the count
+    matches: Option<(u64, usize)>,
+    /// search result asked to show, taken by the next frame
+    reveal: Option<Reveal>
 }
 
-struct FileViewer {
-    filter: String
+struct FileViewer<'a> {
+    query: &'a Query,
+    version: u64
 }
 
-impl egui_dock::TabViewer for FileViewer {
+impl egui_dock::TabViewer for FileViewer<'_> {
     type Tab = FileTab;
 
     fn ui(&mut self, ui: &mut egui::Ui, tab: &mut Self::Tab) {
-        tab.parser.data_view(ui, &self.filter);
+        match tab {
+            FileTab::Data(data) => data.parser.data_view(ui, self.query, data.reveal.take().as_ref()),
+            FileTab::Hex(hex) => hex.view(ui)
+        }
     }
 
     fn title(&mut self, tab: &mut Self::Tab) -> egui::WidgetText {
-        tab.parser.tab_title().into()
+        match tab {
+            FileTab::Data(data) if self.query.is_empty() => data.parser.tab_title().into(),
+            FileTab::Data(data) => {
+                let count = match data.matches {
+                    Some((version, count)) if version == self.version => count,
+                    _ => {
+                        let count = parsers::search(data.parser.as_ref(), self.query).len();
+                        data.matches = Some((self.version, count));
+                        count
+                    }
+                };
+                format!("{} ({})", data.parser.tab_title(), count).into()
+            },
+            FileTab::Hex(hex) => hex.title().into()
+        }
     }
 }
\ No newline at end of file
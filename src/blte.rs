@@ -0,0 +1,219 @@
+use std::{io::{self, Read, Write}, fs::{self, File}, collections::BTreeMap, path::{Path, PathBuf}};
+use flate2::read::ZlibDecoder;
+use serde::Serialize;
+
+use crate::{parsers, toc::{self, CoreToc}};
+
+/// blobs copied out of the data archives keep the archive's 30 byte entry header
+const ARCHIVE_HEADER_SIZE: usize = 0x1E;
+const CHUNK_INFO_SIZE: usize = 24;
+/// written to the output folder, listing the blobs that could not be decoded
+const ERRORS_FILE: &str = "blte_errors.json";
+
+/// a blob that was skipped
+#[derive(Serialize)]
+struct BlobError {
+    file: String,
+    error: String
+}
+
+/// decodes a BLTE blob into its plain payload
+///
+/// handles blobs with a frame table as well as headerless single chunk blobs, with the chunks
+/// either stored raw (`N`), zlib compressed (`Z`) or as nested BLTE frames (`F`)
+pub fn decode(buf: &[u8]) -> io::Result<Vec<u8>> {
+    let buf = match buf.get(ARCHIVE_HEADER_SIZE..ARCHIVE_HEADER_SIZE + 4) {
+        Some(b"BLTE") if buf.get(0..4) != Some(b"BLTE") => &buf[ARCHIVE_HEADER_SIZE..],
+        _ => buf
+    };
+    if buf.len() < 8 || &buf[0..4] != b"BLTE" {
+        return Err(invalid("not a BLTE blob"));
+    }
+    let header_size = be_u32(buf, 4)? as usize;
+
+    let mut out = Vec::new();
+    if header_size == 0 {
+        decode_chunk(&buf[8..], &mut out)?;
+        return Ok(out);
+    }
+
+    let chunk_count = be_u32(buf, 8)? as usize & 0xFFFFFF;
+    let mut pos = header_size;
+    for i in 0..chunk_count {
+        let info = 12 + i*CHUNK_INFO_SIZE;
+        let size = be_u32(buf, info)? as usize;
+        let decoded_size = be_u32(buf, info + 4)? as usize;
+        let chunk = buf.get(pos..pos + size).ok_or_else(|| invalid("BLTE chunk out of range"))?;
+
+        let start = out.len();
+        decode_chunk(chunk, &mut out)?;
+        if out.len() - start != decoded_size {
+            return Err(invalid(&format!("BLTE chunk {} decoded to {} bytes instead of {}", i, out.len() - start, decoded_size)));
+        }
+        pos += size;
+    }
+
+    Ok(out)
+}
+
+fn decode_chunk(chunk: &[u8], out: &mut Vec<u8>) -> io::Result<()> {
+    match chunk.first() {
+        Some(b'N') => out.extend_from_slice(&chunk[1..]),
+        Some(b'Z') => {
+            ZlibDecoder::new(&chunk[1..]).read_to_end(out)?;
+        },
+        Some(b'F') => out.extend(decode(&chunk[1..])?),
+        Some(mode) => return Err(invalid(&format!("unsupported BLTE chunk mode '{}'", *mode as char))),
+        None => ()
+    }
+
+    Ok(())
+}
+
+/// decodes every blob in a folder into `out_path/<extension>` and runs the matching parsers
+///
+/// the extension comes from the blob's name with any `.blte` suffix removed, or, when a toc is
+/// given, from the group of the sno id in the decoded header; blobs that cannot be read or decoded
+/// are skipped and listed in `out_path/blte_errors.json`
+pub fn run(path: &str, out_path: &str, toc_path: Option<&str>) -> io::Result<()> {
+    let toc = match toc_path {
+        Some(toc_path) => Some(CoreToc::read(toc_path)?),
+        None => None
+    };
+
+    let mut folders = BTreeMap::new();
+    let mut errors = Vec::new();
+    for file in fs::read_dir(path)? {
+        let f_u = file?;
+        let file_name = f_u.file_name().to_str().unwrap().to_owned();
+        let payload = match fs::read(f_u.path()).and_then(|x| decode(&x)) {
+            Ok(payload) => payload,
+            Err(e) => {
+                errors.push(BlobError { file: file_name, error: e.to_string() });
+                continue;
+            }
+        };
+
+        let name = file_name.strip_suffix(".blte").unwrap_or(&file_name).to_owned();
+        let named = match Path::new(&name).extension().and_then(|x| x.to_str()) {
+            Some(extension) if parsers::EXTENSIONS.contains(&extension) => Some((extension.to_owned(), name.clone())),
+            _ => payload.get(0xC..0x10)
+                .map(|x| u32::from_le_bytes(x.try_into().unwrap()))
+                .and_then(|id| toc.as_ref().and_then(|x| x.get(id)))
+                .and_then(|entry| toc::group_extension(entry.group).map(|extension| (extension.to_owned(), format!("{}.{}", entry.name, extension))))
+        };
+        let (extension, name) = named.unwrap_or(("unknown".to_owned(), name));
+
+        let folder = Path::new(out_path).join(&extension);
+        fs::create_dir_all(&folder)?;
+        let mut f = File::create(folder.join(name))?;
+        f.write_all(&payload)?;
+        folders.insert(extension, folder);
+    }
+    errors.sort_by(|a, b| a.file.cmp(&b.file));
+    fs::create_dir_all(out_path)?;
+    let json = serde_json::to_string_pretty(&errors)?;
+    let mut log = File::create(Path::new(out_path).join(ERRORS_FILE))?;
+    log.write_all(json.as_bytes())?;
+
+    let folders: Vec<(String, PathBuf)> = folders.into_iter().collect();
+    parsers::run_folders(&folders)
+}
+
+fn be_u32(buf: &[u8], pos: usize) -> io::Result<u32> {
+    match buf.get(pos..pos + 4) {
+        Some(bytes) => Ok(u32::from_be_bytes(bytes.try_into().unwrap())),
+        None => Err(invalid("unexpected end of BLTE data"))
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::{write::ZlibEncoder, Compression};
+
+    use super::*;
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// a blob without a frame table holding one chunk
+    fn single(chunk: &[u8]) -> Vec<u8> {
+        [b"BLTE".as_slice(), &0u32.to_be_bytes(), chunk].concat()
+    }
+
+    /// a blob with a frame table, each chunk with the size it claims to decode to
+    fn framed(chunks: &[(Vec<u8>, usize)]) -> Vec<u8> {
+        let header_size = 12 + chunks.len()*CHUNK_INFO_SIZE;
+        let mut buf = [b"BLTE".as_slice(), &(header_size as u32).to_be_bytes(), &(0x0F000000 | chunks.len() as u32).to_be_bytes()].concat();
+        for (chunk, decoded_size) in chunks {
+            buf.extend((chunk.len() as u32).to_be_bytes());
+            buf.extend((*decoded_size as u32).to_be_bytes());
+            buf.extend([0u8; 16]);
+        }
+        for (chunk, _) in chunks {
+            buf.extend(chunk);
+        }
+        buf
+    }
+
+    fn raw(data: &[u8]) -> Vec<u8> {
+        [b"N".as_slice(), data].concat()
+    }
+
+    fn compressed(data: &[u8]) -> Vec<u8> {
+        [b"Z".as_slice(), &zlib(data)].concat()
+    }
+
+    #[test]
+    fn single_chunks_decode() {
+        assert_eq!(decode(&single(&raw(b"plain"))).unwrap(), b"plain");
+        assert_eq!(decode(&single(&compressed(b"packed packed packed"))).unwrap(), b"packed packed packed");
+
+        let archived = [vec![0xAAu8; ARCHIVE_HEADER_SIZE], single(&raw(b"plain"))].concat();
+        assert_eq!(decode(&archived).unwrap(), b"plain");
+    }
+
+    #[test]
+    fn frames_decode_in_order() {
+        let nested = [b"F".as_slice(), &single(&raw(b"inner"))].concat();
+        let blob = framed(&[(raw(b"first "), 6), (compressed(b"second "), 7), (nested, 5)]);
+        assert_eq!(decode(&blob).unwrap(), b"first second inner");
+    }
+
+    #[test]
+    fn bad_blobs_are_errors() {
+        let wrong_size = framed(&[(raw(b"first"), 5), (compressed(b"second"), 7)]);
+        assert_eq!(decode(&wrong_size).unwrap_err().to_string(), "BLTE chunk 1 decoded to 6 bytes instead of 7");
+
+        let mut truncated = framed(&[(raw(b"first"), 5)]);
+        truncated.pop();
+        assert!(decode(&truncated).is_err());
+        assert!(decode(&single(b"Xdata")).is_err());
+        assert!(decode(&single(b"Znot zlib")).is_err());
+        assert!(decode(b"BLT").is_err());
+        assert!(decode(b"ZZZZ\0\0\0\0Nplain").is_err());
+    }
+
+    #[test]
+    fn run_skips_bad_blobs() {
+        let dir = std::env::temp_dir().join(format!("blte_run_{}", std::process::id()));
+        let (blobs, out) = (dir.join("blobs"), dir.join("out"));
+        fs::create_dir_all(&blobs).unwrap();
+        fs::write(blobs.join("good.bin.blte"), single(&raw(b"payload"))).unwrap();
+        fs::write(blobs.join("bad.blte"), b"not a blob").unwrap();
+
+        run(blobs.to_str().unwrap(), out.to_str().unwrap(), None).unwrap();
+        assert_eq!(fs::read(out.join("unknown").join("good.bin")).unwrap(), b"payload");
+        let errors: serde_json::Value = serde_json::from_slice(&fs::read(out.join(ERRORS_FILE)).unwrap()).unwrap();
+        assert_eq!(errors, serde_json::json!([{ "file": "bad.blte", "error": "not a BLTE blob" }]));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,575 @@
+use std::{io::{self, Read, Seek, Write}, fs::{self, File}, collections::{HashMap, BTreeMap}, path::{Path, PathBuf}};
+
+use crate::{blte, parsers, game_build, sno::PAYLOAD_FOLDERS, toc::{self, CoreToc}, utils};
+
+const KEY_SIZE: usize = 9; // index files only store the first 9 bytes of an encoding key
+const DATA_HEADER_SIZE: usize = 0x1E;
+const FOLDER_NODE: u32 = 0x80000000;
+const SKIPPED_FOLDERS: [&str; 1] = ["child"];
+
+#[derive(Clone, Copy)]
+struct IndexEntry {
+    archive: u32,
+    offset: u32,
+    size: u32
+}
+
+/// a local game installation's CASC storage, opened read only
+pub struct Casc {
+    install_path: PathBuf,
+    data_path: PathBuf,
+    index: HashMap<[u8; KEY_SIZE], IndexEntry>,
+    /// every file of the virtual file system, by path, with the encoding keys of its spans
+    pub files: BTreeMap<String, Vec<Vec<u8>>>
+}
+
+impl Casc {
+    pub fn open(install_path: &str) -> io::Result<Self> {
+        let install_path = Path::new(install_path);
+        let data_path = install_path.join("Data");
+        let build_info = game_build::active_build_info(&install_path.join(".build.info"))?;
+        let build_key = build_info.get("Build Key").ok_or_else(|| invalid(".build.info has no Build Key"))?;
+        let config = Casc::read_config(&data_path, build_key)?;
+
+        let mut casc = Self {
+            index: Casc::read_indices(&data_path.join("data"))?,
+            install_path: install_path.to_owned(),
+            data_path,
+            files: BTreeMap::new()
+        };
+
+        let root_ekey = match config.get("vfs-root") {
+            Some(keys) if keys.len() > 1 => hex_to_bytes(&keys[1])?,
+            Some(keys) => {
+                let encoding_ekey = config.get("encoding").and_then(|x| x.get(1))
+                    .ok_or_else(|| invalid("build config has no encoding key"))?;
+                let encoding = casc.read_ekey(&hex_to_bytes(encoding_ekey)?)?;
+                let encoding = Casc::parse_encoding(&encoding)?;
+                let ckey = hex_to_bytes(&keys[0])?;
+                encoding.get(&ckey).cloned().ok_or_else(|| invalid("vfs root is missing from the encoding table"))?
+            },
+            None => return Err(invalid("build config has no vfs-root"))
+        };
+
+        let mut vfs_keys = Vec::new();
+        for (key, values) in config.iter() {
+            if key.starts_with("vfs-") && key != "vfs-root" && values.len() > 1 {
+                vfs_keys.extend(short_key(&hex_to_bytes(&values[1])?));
+            }
+        }
+        let is_vfs = |spans: &Vec<Vec<u8>>| spans.len() == 1 && short_key(&spans[0]).map(|x| vfs_keys.contains(&x)).unwrap_or(false);
+
+        let root = casc.read_ekey(&root_ekey)?;
+        let mut files = BTreeMap::new();
+        Tvfs::parse(&root, "", &mut files)?;
+
+        // the root links to one sub-file-system per vfs entry of the build config
+        for (path, spans) in files {
+            if is_vfs(&spans) {
+                let sub_root = casc.read_ekey(&spans[0])?;
+                Tvfs::parse(&sub_root, &path, &mut casc.files)?;
+            }
+            else {
+                casc.files.insert(path, spans);
+            }
+        }
+
+        Ok(casc)
+    }
+
+    /// reads `Data/config/ab/cd/abcd...` into key and space separated values
+    fn read_config(data_path: &Path, key: &str) -> io::Result<HashMap<String, Vec<String>>> {
+        let path = match (key.get(0..2), key.get(2..4)) {
+            (Some(a), Some(b)) => data_path.join("config").join(a).join(b).join(key),
+            _ => return Err(invalid("config key is too short"))
+        };
+        let text = fs::read_to_string(path)?;
+
+        let config = text.lines()
+            .filter(|x| !x.starts_with('#'))
+            .filter_map(|x| x.split_once('='))
+            .map(|(k, v)| (k.trim().to_owned(), v.split_whitespace().map(|x| x.to_owned()).collect()))
+            .collect();
+
+        Ok(config)
+    }
+
+    /// reads the newest version of every `.idx` bucket in `Data/data`
+    fn read_indices(path: &Path) -> io::Result<HashMap<[u8; KEY_SIZE], IndexEntry>> {
+        let mut newest: HashMap<String, (u32, PathBuf)> = HashMap::new();
+        for file in fs::read_dir(path)? {
+            let file_path = file?.path();
+            let name = file_path.file_name().and_then(|x| x.to_str()).unwrap_or_default().to_owned();
+            if !name.ends_with(".idx") || name.len() < 14 {
+                continue;
+            }
+            let bucket = name[0..2].to_owned();
+            let version = u32::from_str_radix(&name[2..10], 16).unwrap_or(0);
+            if newest.get(&bucket).map(|x| x.0 < version).unwrap_or(true) {
+                newest.insert(bucket, (version, file_path));
+            }
+        }
+
+        let mut index = HashMap::new();
+        for (_, path) in newest.values() {
+            let mut f = File::open(path)?;
+            let header_hash_size = utils::read_u32(&mut f)?;
+            utils::padding(&mut f, 9)?; // header hash, version, bucket, extra bytes and size length
+            let offset_size = read_u8(&mut f)? as usize;
+            let key_size = read_u8(&mut f)? as usize;
+            let offset_bits = read_u8(&mut f)? as u32;
+            if offset_bits >= 64 {
+                return Err(invalid("index offset bits out of range"));
+            }
+
+            let entries_offset = (8 + header_hash_size as u64 + 0xF) & !0xF;
+            utils::go_to(&mut f, entries_offset)?;
+            let entries_size = utils::read_u32(&mut f)? as usize;
+            utils::padding(&mut f, 4)?;
+            if entries_offset + 8 + entries_size as u64 > f.metadata()?.len() {
+                return Err(invalid("index entries run past the end of the file"));
+            }
+
+            let entry_size = key_size + offset_size + 4;
+            let mut entries = vec![0u8; entries_size - entries_size % entry_size];
+            f.read_exact(&mut entries)?;
+
+            for entry in entries.chunks_exact(entry_size) {
+                let key = match short_key(entry) {
+                    Some(key) => key,
+                    None => continue
+                };
+                let location = entry[key_size..key_size + offset_size].iter().fold(0u64, |acc, x| acc << 8 | *x as u64);
+                let size = u32::from_le_bytes(entry[key_size + offset_size..].try_into().unwrap());
+                index.entry(key).or_insert(IndexEntry {
+                    archive: (location >> offset_bits) as u32,
+                    offset: (location & ((1u64 << offset_bits) - 1)) as u32,
+                    size
+                });
+            }
+        }
+
+        Ok(index)
+    }
+
+    /// maps content keys to encoding keys
+    fn parse_encoding(buf: &[u8]) -> io::Result<HashMap<Vec<u8>, Vec<u8>>> {
+        if buf.len() < 22 || &buf[0..2] != b"EN" {
+            return Err(invalid("not an encoding file"));
+        }
+        let ckey_size = buf[3] as usize;
+        let ekey_size = buf[4] as usize;
+        let ckey_page_size = be_u16(buf, 5)? as usize * 1024;
+        let ckey_page_count = be_u32(buf, 9)? as usize;
+        let espec_block_size = be_u32(buf, 18)? as usize;
+
+        let page_index = 22 + espec_block_size;
+        let mut page = page_index + ckey_page_count * (ckey_size + 16);
+        let mut encoding = HashMap::new();
+        for _ in 0..ckey_page_count {
+            let end = (page + ckey_page_size).min(buf.len());
+            let mut pos = page;
+            while pos < end {
+                let key_count = buf[pos] as usize;
+                if key_count == 0 {
+                    break;
+                }
+                let ckey_pos = pos + 6; // key count and a 40 bit file size
+                let ekey_pos = ckey_pos + ckey_size;
+                if ekey_pos + ekey_size > end {
+                    break;
+                }
+                encoding.insert(buf[ckey_pos..ekey_pos].to_vec(), buf[ekey_pos..ekey_pos + ekey_size].to_vec());
+                pos = ekey_pos + key_count * ekey_size;
+            }
+            page += ckey_page_size;
+        }
+
+        Ok(encoding)
+    }
+
+    /// reads and decodes one file from the data archives
+    pub fn read_ekey(&self, ekey: &[u8]) -> io::Result<Vec<u8>> {
+        let key = short_key(ekey).ok_or_else(|| invalid("encoding key is too short"))?;
+        let entry = *self.index.get(&key).ok_or_else(|| invalid("encoding key is not in the local indices"))?;
+
+        let mut f = File::open(self.data_path.join("data").join(format!("data.{:03}", entry.archive)))?;
+        f.seek(io::SeekFrom::Start(entry.offset as u64))?;
+        let mut buf = vec![0u8; entry.size as usize];
+        f.read_exact(&mut buf)?;
+
+        blte::decode(buf.get(DATA_HEADER_SIZE..).ok_or_else(|| invalid("archive entry is too short"))?)
+    }
+
+    /// reads every span of a file and joins them
+    pub fn read_file(&self, path: &str) -> io::Result<Vec<u8>> {
+        let spans = self.files.get(path).ok_or_else(|| invalid("file is not in the storage"))?;
+        let mut data = Vec::new();
+        for ekey in spans {
+            data.extend(self.read_ekey(ekey)?);
+        }
+
+        Ok(data)
+    }
+
+    /// writes every file a parser understands to `out_path/meta/<extension>/<name>.<extension>`, and
+    /// payloads of split files to `out_path/payload/...`, naming sno files through the storage's
+    /// CoreTOC, and returns the meta folders written to
+    ///
+    /// `.build.info` is copied along so parsers run on the output later pick the matching record sizes
+    pub fn extract(&self, out_path: &str) -> io::Result<Vec<(String, PathBuf)>> {
+        fs::create_dir_all(out_path)?;
+        fs::copy(self.install_path.join(".build.info"), Path::new(out_path).join(".build.info"))?;
+
+        let toc = match self.files.keys().find(|x| x.to_lowercase().ends_with("coretoc.dat")) {
+            Some(path) => Some(CoreToc::parse(&self.read_file(path)?)?),
+            None => None
+        };
+
+        let mut folders = BTreeMap::new();
+        for path in self.files.keys() {
+            let parts: Vec<&str> = path.split(['/', '\\', ':']).collect();
+            if parts.iter().any(|x| SKIPPED_FOLDERS.contains(&x.to_lowercase().as_str())) {
+                continue;
+            }
+            let kind = parts.iter()
+                .map(|x| x.to_lowercase())
+                .find(|x| PAYLOAD_FOLDERS.contains(&x.as_str()))
+                .unwrap_or_else(|| "meta".to_owned());
+            let file_name = parts.last().copied().unwrap_or_default();
+            let named = match Path::new(file_name).extension().and_then(|x| x.to_str()) {
+                Some(extension) if parsers::EXTENSIONS.contains(&extension) => Some((extension.to_owned(), file_name.to_owned())),
+                _ => {
+                    let id = Path::new(file_name).file_stem().and_then(|x| x.to_str()).and_then(|x| x.parse::<u32>().ok());
+                    id.and_then(|id| toc.as_ref().and_then(|x| x.get(id)))
+                        .and_then(|entry| toc::group_extension(entry.group).map(|extension| (extension.to_owned(), format!("{}.{}", entry.name, extension))))
+                }
+            };
+
+            if let Some((extension, name)) = named {
+                let folder = Path::new(out_path).join(&kind).join(&extension);
+                fs::create_dir_all(&folder)?;
+                let mut f = File::create(folder.join(name))?;
+                f.write_all(&self.read_file(path)?)?;
+                if kind == "meta" {
+                    folders.insert(extension, folder);
+                }
+            }
+        }
+
+        Ok(folders.into_iter().collect())
+    }
+}
+
+/// extracts every supported file from a game installation and runs its parser on it
+pub fn run(install_path: &str, out_path: &str) -> io::Result<()> {
+    let casc = Casc::open(install_path)?;
+    parsers::run_folders(&casc.extract(out_path)?)
+}
+
+/// the virtual file system root format, a prefix tree of paths pointing at file spans
+struct Tvfs<'a> {
+    buf: &'a [u8],
+    ekey_size: usize,
+    vfs_table_offset: usize,
+    cft_table_offset: usize,
+    cft_offset_size: usize
+}
+
+impl<'a> Tvfs<'a> {
+    fn parse(buf: &'a [u8], prefix: &str, files: &mut BTreeMap<String, Vec<Vec<u8>>>) -> io::Result<()> {
+        if buf.len() < 38 || &buf[0..4] != b"TVFS" {
+            return Err(invalid("not a TVFS root"));
+        }
+        let path_table_offset = be_u32(buf, 12)? as usize;
+        let path_table_size = be_u32(buf, 16)? as usize;
+        let cft_table_size = be_u32(buf, 32)?;
+        let tvfs = Tvfs {
+            buf,
+            ekey_size: buf[6] as usize,
+            vfs_table_offset: be_u32(buf, 20)? as usize,
+            cft_table_offset: be_u32(buf, 28)? as usize,
+            cft_offset_size: match cft_table_size {
+                x if x > 0xFFFFFF => 4,
+                x if x > 0xFFFF => 3,
+                x if x > 0xFF => 2,
+                _ => 1
+            }
+        };
+
+        let mut path = prefix.to_owned();
+        push_separator(&mut path);
+        tvfs.parse_paths(path_table_offset, path_table_offset + path_table_size, &mut path, files)
+    }
+
+    fn parse_paths(&self, mut pos: usize, end: usize, path: &mut String, files: &mut BTreeMap<String, Vec<Vec<u8>>>) -> io::Result<()> {
+        let saved = path.len();
+        let end = end.min(self.buf.len());
+        while pos < end {
+            if self.buf[pos] == 0 {
+                push_separator(path);
+                pos += 1;
+            }
+            if pos < end && self.buf[pos] != 0xFF {
+                let len = self.buf[pos] as usize;
+                let name = self.buf.get(pos + 1..pos + 1 + len).ok_or_else(|| invalid("TVFS name out of range"))?;
+                path.push_str(&String::from_utf8_lossy(name));
+                pos += 1 + len;
+            }
+            if pos < end && self.buf[pos] == 0 {
+                push_separator(path);
+                pos += 1;
+            }
+            if pos >= end {
+                break;
+            }
+            if self.buf[pos] != 0xFF {
+                push_separator(path);
+                continue;
+            }
+
+            let value = be_u32(self.buf, pos + 1)?;
+            pos += 5;
+            if value & FOLDER_NODE != 0 {
+                let folder_size = ((value & !FOLDER_NODE) as usize).saturating_sub(4);
+                self.parse_paths(pos, pos + folder_size, path, files)?;
+                pos += folder_size;
+            }
+            else {
+                files.insert(path.clone(), self.spans(value as usize)?);
+            }
+            path.truncate(saved);
+        }
+
+        Ok(())
+    }
+
+    /// reads the encoding keys of every span of the file at `offset` in the vfs table
+    fn spans(&self, offset: usize) -> io::Result<Vec<Vec<u8>>> {
+        let mut pos = self.vfs_table_offset + offset;
+        let span_count = *self.buf.get(pos).ok_or_else(|| invalid("TVFS file out of range"))? as usize;
+        pos += 1;
+
+        let mut spans = Vec::new();
+        for _ in 0..span_count {
+            pos += 8; // offset and size of the span within the file
+            let cft_offset = self.buf.get(pos..pos + self.cft_offset_size)
+                .ok_or_else(|| invalid("TVFS span out of range"))?
+                .iter()
+                .fold(0usize, |acc, x| acc << 8 | *x as usize);
+            pos += self.cft_offset_size;
+
+            let key = self.cft_table_offset + cft_offset;
+            spans.push(self.buf.get(key..key + self.ekey_size).ok_or_else(|| invalid("TVFS key out of range"))?.to_vec());
+        }
+
+        Ok(spans)
+    }
+}
+
+fn short_key(key: &[u8]) -> Option<[u8; KEY_SIZE]> {
+    key.get(..KEY_SIZE).map(|x| x.try_into().unwrap())
+}
+
+fn push_separator(path: &mut String) {
+    if !path.is_empty() && !path.ends_with('/') {
+        path.push('/');
+    }
+}
+
+fn read_u8(f: &mut File) -> io::Result<u8> {
+    let mut buff = [0; 1];
+    f.read_exact(&mut buff)?;
+
+    Ok(buff[0])
+}
+
+fn be_u16(buf: &[u8], pos: usize) -> io::Result<u16> {
+    match buf.get(pos..pos + 2) {
+        Some(bytes) => Ok(u16::from_be_bytes(bytes.try_into().unwrap())),
+        None => Err(invalid("unexpected end of data"))
+    }
+}
+
+fn be_u32(buf: &[u8], pos: usize) -> io::Result<u32> {
+    match buf.get(pos..pos + 4) {
+        Some(bytes) => Ok(u32::from_be_bytes(bytes.try_into().unwrap())),
+        None => Err(invalid("unexpected end of data"))
+    }
+}
+
+fn hex_to_bytes(hex: &str) -> io::Result<Vec<u8>> {
+    (0..hex.len() / 2)
+        .map(|i| hex.get(i*2..i*2 + 2).and_then(|x| u8::from_str_radix(x, 16).ok()).ok_or_else(|| invalid("invalid hex key")))
+        .collect()
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUILD_KEY: &str = "abcdef0123456789";
+    const FILES: [(&str, &[u8]); 2] = [("Test.stl", b"string list bytes"), ("Other.dat", b"not a sno file")];
+
+    /// a name and what it breaks in a fixture
+    type Corruption = (&'static str, fn(&Path));
+
+    fn key(n: u8) -> Vec<u8> {
+        vec![n; 16]
+    }
+
+    fn hex(key: &[u8]) -> String {
+        key.iter().map(|x| format!("{:02x}", x)).collect()
+    }
+
+    fn blte(payload: &[u8]) -> Vec<u8> {
+        [b"BLTE".as_slice(), &[0u8; 4], b"N", payload].concat()
+    }
+
+    /// an encoding table with a single page mapping one content key
+    fn encoding(ckey: &[u8], ekey: &[u8]) -> Vec<u8> {
+        let mut buf = vec![0u8; 22];
+        buf[0..2].copy_from_slice(b"EN");
+        buf[2] = 1;
+        buf[3] = 16;
+        buf[4] = 16;
+        buf[5..7].copy_from_slice(&1u16.to_be_bytes());
+        buf[9..13].copy_from_slice(&1u32.to_be_bytes());
+        buf.extend([0u8; 32]);
+        let mut page = vec![1u8, 0, 0, 0, 0, 0];
+        page.extend(ckey);
+        page.extend(ekey);
+        page.resize(1024, 0);
+        buf.extend(page);
+        buf
+    }
+
+    /// a root listing the files at the top level, each with one span
+    fn tvfs(files: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut paths = Vec::new();
+        let mut vfs = Vec::new();
+        let mut cft: Vec<u8> = Vec::new();
+        for (i, (name, ekey)) in files.iter().enumerate() {
+            paths.push(name.len() as u8);
+            paths.extend(name.as_bytes());
+            paths.push(0xFF);
+            paths.extend((vfs.len() as u32).to_be_bytes());
+            vfs.extend([1u8, 0, 0, 0, 0, 0, 0, 0, 0, (i*16) as u8]);
+            cft.extend(ekey);
+        }
+
+        let mut buf = vec![0u8; 38];
+        buf[0..4].copy_from_slice(b"TVFS");
+        buf[4] = 1;
+        buf[5] = 38;
+        buf[6] = 16;
+        let tables = [(38, paths.len()), (38 + paths.len(), vfs.len()), (38 + paths.len() + vfs.len(), cft.len())];
+        for (i, (offset, size)) in tables.iter().enumerate() {
+            buf[12 + i*8..16 + i*8].copy_from_slice(&(*offset as u32).to_be_bytes());
+            buf[16 + i*8..20 + i*8].copy_from_slice(&(*size as u32).to_be_bytes());
+        }
+        buf.extend(paths);
+        buf.extend(vfs);
+        buf.extend(cft);
+        buf
+    }
+
+    /// writes an installation with a build config, an encoding table, a TVFS root and the files
+    fn fixture(name: &str) -> PathBuf {
+        let install = std::env::temp_dir().join(format!("casc_test_{}_{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&install);
+        let config_path = install.join("Data/config").join(&BUILD_KEY[0..2]).join(&BUILD_KEY[2..4]);
+        fs::create_dir_all(&config_path).unwrap();
+        fs::create_dir_all(install.join("Data/data")).unwrap();
+        fs::write(install.join(".build.info"), format!("Branch!STRING:0|Active!DEC:1|Build Key!HEX:16|Version!STRING:0\neu|1|{}|1.0.0.12345\n", BUILD_KEY)).unwrap();
+
+        let (encoding_ekey, root_ckey, root_ekey) = (key(1), key(2), key(3));
+        fs::write(config_path.join(BUILD_KEY), format!("# build config\nencoding = {} {}\nvfs-root = {}\n", hex(&key(9)), hex(&encoding_ekey), hex(&root_ckey))).unwrap();
+
+        let files: Vec<(&str, Vec<u8>)> = FILES.iter().enumerate().map(|(i, x)| (x.0, key(4 + i as u8))).collect();
+        let mut blobs = vec![(encoding_ekey, encoding(&root_ckey, &root_ekey)), (root_ekey, tvfs(&files))];
+        blobs.extend(files.iter().zip(FILES).map(|(x, file)| (x.1.clone(), file.1.to_vec())));
+
+        let mut data = Vec::new();
+        let mut entries = Vec::new();
+        for (ekey, content) in blobs {
+            let blob = [vec![0u8; DATA_HEADER_SIZE], blte(&content)].concat();
+            entries.extend(&ekey[..KEY_SIZE]);
+            entries.extend(&(data.len() as u64).to_be_bytes()[3..]);
+            entries.extend((blob.len() as u32).to_le_bytes());
+            data.extend(blob);
+        }
+        fs::write(install.join("Data/data/data.000"), data).unwrap();
+
+        let mut idx = vec![0u8; 16];
+        idx[0..4].copy_from_slice(&8u32.to_le_bytes());
+        idx[13] = 5;
+        idx[14] = KEY_SIZE as u8;
+        idx[15] = 30;
+        idx.extend((entries.len() as u32).to_le_bytes());
+        idx.extend([0u8; 4]);
+        idx.extend(entries);
+        fs::write(install.join("Data/data/0000000001.idx"), idx).unwrap();
+
+        install
+    }
+
+    #[test]
+    fn opens_reads_and_extracts() {
+        let install = fixture("valid");
+        let casc = Casc::open(&install.display().to_string()).unwrap();
+        assert_eq!(casc.files.keys().collect::<Vec<_>>(), ["Other.dat", "Test.stl"]);
+        for (name, content) in FILES {
+            assert_eq!(casc.read_file(name).unwrap(), content);
+        }
+        assert!(casc.read_file("Missing.stl").is_err());
+
+        let out = install.join("out");
+        let folders = casc.extract(&out.display().to_string()).unwrap();
+        assert_eq!(folders, [("stl".to_owned(), out.join("meta").join("stl"))]);
+        assert_eq!(fs::read(out.join("meta/stl/Test.stl")).unwrap(), FILES[0].1);
+        assert!(out.join(".build.info").is_file());
+
+        let _ = fs::remove_dir_all(&install);
+    }
+
+    #[test]
+    fn corrupt_storage_is_an_error() {
+        let corruptions: [Corruption; 5] = [
+            ("truncated_data", |x| {
+                let data = fs::read(x.join("Data/data/data.000")).unwrap();
+                fs::write(x.join("Data/data/data.000"), &data[..data.len()/2]).unwrap();
+            }),
+            ("bad_blte", |x| {
+                let mut data = fs::read(x.join("Data/data/data.000")).unwrap();
+                data[DATA_HEADER_SIZE..DATA_HEADER_SIZE + 4].copy_from_slice(b"XXXX");
+                fs::write(x.join("Data/data/data.000"), data).unwrap();
+            }),
+            ("bad_offset_bits", |x| {
+                let mut idx = fs::read(x.join("Data/data/0000000001.idx")).unwrap();
+                idx[15] = 64;
+                fs::write(x.join("Data/data/0000000001.idx"), idx).unwrap();
+            }),
+            ("oversized_index", |x| {
+                let mut idx = fs::read(x.join("Data/data/0000000001.idx")).unwrap();
+                idx[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+                fs::write(x.join("Data/data/0000000001.idx"), idx).unwrap();
+            }),
+            ("bad_config", |x| {
+                let config = x.join("Data/config").join(&BUILD_KEY[0..2]).join(&BUILD_KEY[2..4]).join(BUILD_KEY);
+                fs::write(config, "encoding = zz\u{e9} \u{e9}zz\nvfs-root = 02\n").unwrap();
+            })
+        ];
+
+        for (name, corrupt) in corruptions {
+            let install = fixture(name);
+            corrupt(&install);
+            assert!(Casc::open(&install.display().to_string()).is_err(), "{} opened", name);
+            let _ = fs::remove_dir_all(&install);
+        }
+    }
+}
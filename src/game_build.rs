@@ -0,0 +1,115 @@
+use std::{io, fs, collections::HashMap, path::Path, sync::atomic::{AtomicU32, Ordering}};
+
+const BUILD_INFO: &str = ".build.info";
+
+/// build forced from the command line, 0 when the build should be detected
+static BUILD_OVERRIDE: AtomicU32 = AtomicU32::new(0);
+
+/// sizes of the records read by the built in parsers, from the first game build they apply to
+pub struct Records {
+    pub build: u32,
+    pub stl_pair: u32,
+    pub aff_value: u32,
+    pub skl_node: u32,
+    pub qst_step: u32,
+    pub mon_attribute: u32,
+    pub ach_criterion: u32
+}
+
+/// ordered by build, add a row when a patch changes a struct size
+const RECORDS: [Records; 1] = [
+    Records {
+        build: 0,
+        stl_pair: 40,
+        aff_value: 44,
+        skl_node: 64,
+        qst_step: 40,
+        mon_attribute: 16,
+        ach_criterion: 24
+    }
+];
+
+pub fn set_override(build: u32) {
+    BUILD_OVERRIDE.store(build, Ordering::Relaxed);
+}
+
+/// the build forced with `--build`, or the one in the nearest `.build.info` in the folder or above it
+pub fn detect(path: &Path) -> Option<u32> {
+    match BUILD_OVERRIDE.load(Ordering::Relaxed) {
+        0 => path.ancestors()
+            .map(|x| x.join(BUILD_INFO))
+            .find(|x| x.is_file())
+            .and_then(|x| read_build(&x).ok()),
+        build => Some(build)
+    }
+}
+
+/// record sizes for a build, or for the latest known build when it could not be detected
+pub fn records(build: Option<u32>) -> &'static Records {
+    records_in(&RECORDS, build)
+}
+
+fn records_in(table: &[Records], build: Option<u32>) -> &Records {
+    let build = build.unwrap_or(u32::MAX);
+    table.iter().rev().find(|x| x.build <= build).unwrap_or(&table[0])
+}
+
+/// reads the build number, the last part of the `Version` column, from a `.build.info` file
+pub fn read_build(path: &Path) -> io::Result<u32> {
+    let info = active_build_info(path)?;
+    info.get("Version")
+        .and_then(|x| x.rsplit('.').next())
+        .and_then(|x| x.parse::<u32>().ok())
+        .ok_or_else(|| invalid(".build.info has no build number"))
+}
+
+/// columns of the active row of a `.build.info` file, by name without the `!TYPE:size` suffix
+pub fn active_build_info(path: &Path) -> io::Result<HashMap<String, String>> {
+    let build_info = fs::read_to_string(path)?;
+    let mut lines = build_info.lines().filter(|x| !x.trim().is_empty());
+    let header: Vec<&str> = lines.next().ok_or_else(|| invalid("empty .build.info"))?
+        .split('|')
+        .map(|x| x.split('!').next().unwrap_or(x))
+        .collect();
+    let active_column = header.iter().position(|x| *x == "Active");
+
+    for line in lines {
+        let row: Vec<&str> = line.split('|').collect();
+        let active = active_column.map(|x| row.get(x) == Some(&"1")).unwrap_or(true);
+        if active {
+            return Ok(header.iter().zip(row).map(|(k, v)| (k.to_string(), v.to_string())).collect());
+        }
+    }
+
+    Err(invalid(".build.info has no active build"))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(build: u32, aff_value: u32) -> Records {
+        Records { build, aff_value, ..RECORDS[0] }
+    }
+
+    #[test]
+    fn records_follow_the_build() {
+        let table = [row(0, 44), row(40000, 48)];
+        assert_eq!(records_in(&table, Some(1)).aff_value, 44);
+        assert_eq!(records_in(&table, Some(39999)).aff_value, 44);
+        assert_eq!(records_in(&table, Some(40000)).aff_value, 48);
+        assert_eq!(records_in(&table, None).aff_value, 48);
+    }
+
+    #[test]
+    fn build_number_is_read_from_the_active_row() {
+        let path = std::env::temp_dir().join(format!("build_info_test_{}", std::process::id()));
+        fs::write(&path, "Branch!STRING:0|Active!DEC:1|Version!STRING:0\nus|0|1.0.0.100\neu|1|1.2.3.44852\n").unwrap();
+        assert_eq!(read_build(&path).unwrap(), 44852);
+        let _ = fs::remove_file(&path);
+    }
+}
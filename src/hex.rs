@@ -0,0 +1,164 @@
+use std::{io, path::Path};
+use egui::{Color32, RichText};
+use itertools::Itertools;
+
+use crate::{parsers, game_build, sno::{SnoReader, Span, SpanKind}};
+
+const BYTES_PER_ROW: usize = 16;
+/// struct colours, picked in the order the structs are first read
+const PALETTE: [(u8, u8, u8); 6] = [(60, 90, 150), (50, 120, 80), (120, 80, 140), (140, 110, 50), (50, 120, 130), (130, 70, 80)];
+const PADDING_COLOR: Color32 = Color32::from_rgb(70, 70, 70);
+const UNKNOWN_COLOR: Color32 = Color32::from_rgb(170, 100, 20);
+const GAP_COLOR: Color32 = Color32::from_rgb(150, 30, 30);
+
+/// a file's bytes, coloured by what its parser read from them
+pub struct HexView {
+    file_name: String,
+    data: Vec<u8>,
+    spans: Vec<Span>,
+    /// span each byte belongs to, the last read of a byte wins
+    owners: Vec<Option<usize>>,
+    labels: Vec<&'static str>,
+    /// bytes of the meta file that nothing read
+    gaps: usize,
+    error: Option<String>
+}
+
+impl HexView {
+    /// traces the extension's parser over one file, keeping what it read even if it failed half way
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file_name = path.file_name().and_then(|x| x.to_str()).unwrap_or_default().to_owned();
+        let extension = path.extension().and_then(|x| x.to_str()).unwrap_or_default();
+        let mut f = SnoReader::open(path)?;
+        f.trace();
+
+        let error = match parsers::from_extension(extension) {
+            Ok(Some(mut parser)) => parser.read_file(&file_name, &mut f, game_build::detect(path)).err().map(|x| x.to_string()),
+            Ok(None) => Some(format!("no parser for .{}, nothing is annotated", extension)),
+            Err(e) => Some(e.to_string())
+        };
+
+        let spans = f.take_trace();
+        let data = f.data().to_vec();
+        let mut owners = vec![None; data.len()];
+        for (i, span) in spans.iter().enumerate() {
+            let end = ((span.start + span.len) as usize).min(data.len());
+            for owner in owners.iter_mut().take(end).skip(span.start as usize) {
+                *owner = Some(i);
+            }
+        }
+        let labels = spans.iter().map(|x| x.label).unique().collect();
+        let gaps = owners[..f.meta().len()].iter().filter(|x| x.is_none()).count();
+
+        Ok(Self {
+            file_name,
+            data,
+            spans,
+            owners,
+            labels,
+            gaps,
+            error
+        })
+    }
+
+    pub fn title(&self) -> String {
+        format!("hex: {}", self.file_name)
+    }
+
+    fn color(&self, pos: usize) -> Color32 {
+        let span = match self.owners[pos] {
+            Some(i) => &self.spans[i],
+            None => return GAP_COLOR
+        };
+
+        match span.kind {
+            SpanKind::Padding => PADDING_COLOR,
+            SpanKind::Unknown => UNKNOWN_COLOR,
+            SpanKind::Field => {
+                let label = self.labels.iter().position(|x| *x == span.label).unwrap_or(0);
+                let (r, g, b) = PALETTE[label % PALETTE.len()];
+                let shade = match span.record % 2 { // neighbouring records of one struct alternate
+                    0 => 0,
+                    _ => 25
+                };
+                Color32::from_rgb(r.saturating_add(shade), g.saturating_add(shade), b.saturating_add(shade))
+            }
+        }
+    }
+
+    fn tooltip(&self, ui: &mut egui::Ui, pos: usize) {
+        ui.strong(format!("offset {} (0x{:X})", pos, pos));
+        match self.owners[pos].map(|x| &self.spans[x]) {
+            Some(span) => {
+                let kind = match span.kind {
+                    SpanKind::Field => "read",
+                    SpanKind::Padding => "skipped",
+                    SpanKind::Unknown => "unknown"
+                };
+                ui.label(format!("{} #{}, {} 0x{:X}..0x{:X}", span.label, span.record, kind, span.start, span.start + span.len));
+            },
+            None => {
+                ui.label("not read by the parser");
+            }
+        }
+
+        if let Some(bytes) = self.data.get(pos..pos + 4) {
+            let bytes: [u8; 4] = bytes.try_into().unwrap();
+            let value = u32::from_le_bytes(bytes);
+            ui.label(format!("u32: {} (0x{:X})", value, value));
+            ui.label(format!("i32: {}", value as i32));
+            ui.label(format!("f32: {}", f32::from_le_bytes(bytes)));
+        }
+        let text: String = self.data[pos..].iter()
+            .take_while(|x| **x != 0)
+            .take(64)
+            .map(|x| *x as char)
+            .collect();
+        if !text.is_empty() && text.chars().all(|c| !c.is_control()) {
+            ui.label(format!("string: {}", text));
+        }
+    }
+
+    pub fn view(&self, ui: &mut egui::Ui) {
+        ui.horizontal_wrapped(|h| {
+            h.strong(&self.file_name);
+            for (i, label) in self.labels.iter().enumerate() {
+                let (r, g, b) = PALETTE[i % PALETTE.len()];
+                h.label(RichText::new(*label).background_color(Color32::from_rgb(r, g, b)));
+            }
+            h.label(RichText::new("padding").background_color(PADDING_COLOR));
+            h.label(RichText::new("unknown").background_color(UNKNOWN_COLOR));
+            h.label(RichText::new(format!("not read: {} bytes", self.gaps)).background_color(GAP_COLOR));
+        });
+        if let Some(error) = &self.error {
+            ui.colored_label(Color32::RED, error);
+        }
+        ui.separator();
+
+        let rows = self.data.len().div_ceil(BYTES_PER_ROW);
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        egui::ScrollArea::both().show_rows(ui, row_height, rows, |ui, row_range| {
+            for row in row_range {
+                let start = row*BYTES_PER_ROW;
+                let end = (start + BYTES_PER_ROW).min(self.data.len());
+                ui.horizontal(|h| {
+                    h.spacing_mut().item_spacing.x = 3f32;
+                    h.monospace(format!("{:08X}", start));
+                    for pos in start..end {
+                        let text = RichText::new(format!("{:02X}", self.data[pos]))
+                            .monospace()
+                            .background_color(self.color(pos));
+                        h.label(text).on_hover_ui(|ui| self.tooltip(ui, pos));
+                    }
+                    let ascii: String = self.data[start..end].iter()
+                        .map(|x| match x.is_ascii_graphic() {
+                            true => *x as char,
+                            false => '.'
+                        })
+                        .collect();
+                    h.monospace(format!("{:width$} {}", "", ascii, width = (BYTES_PER_ROW - (end - start))*3));
+                });
+            }
+        });
+    }
+}
@@ -0,0 +1,411 @@
+use std::{io::{self, Write}, fs::{self, File}, collections::{BTreeMap, HashMap, HashSet}, path::Path, time::UNIX_EPOCH, sync::atomic::{AtomicBool, Ordering}};
+use serde::{Serialize, Deserialize};
+
+use crate::{utils, parsers::{Parser, stl::Stl, aff::Aff}, query::{self, Entry, Query}};
+
+/// bump when the stored index changes
+const INDEX_VERSION: u32 = 2;
+/// positions left between the words of a key and those of its value, so phrases do not span both
+const FIELD_GAP: u32 = 100;
+
+static PERSIST: AtomicBool = AtomicBool::new(true);
+
+/// whether indexes are saved next to the json they were built from
+pub fn set_persist(enabled: bool) {
+    PERSIST.store(enabled, Ordering::Relaxed);
+}
+
+pub fn persist() -> bool {
+    PERSIST.load(Ordering::Relaxed)
+}
+
+/// size and modification time of the json an index was built from, a stored index is only used while they match
+#[derive(Serialize, Deserialize, PartialEq)]
+struct Source {
+    len: u64,
+    modified: u64
+}
+
+impl Source {
+    fn of(path: &str) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_secs();
+
+        Some(Self { len: metadata.len(), modified })
+    }
+}
+
+/// an inverted index over the words of a dataset: every lowercase word with the records and positions it is at
+#[derive(Serialize, Deserialize)]
+pub struct Index {
+    version: u32,
+    source: Option<Source>,
+    /// file and field key of each record, the file name and hashes make a record with an empty key
+    records: Vec<(String, String)>,
+    words: BTreeMap<String, Vec<(u32, u32)>>,
+    /// hashes are kept out of the words and only found by an exact number, like searches without an index
+    hashes: BTreeMap<u32, Vec<u32>>
+}
+
+enum Term {
+    /// part of a word, so a search finds the same records with and without an index
+    Word(String),
+    /// `word*`
+    Prefix(String),
+    /// `word~`, any word within the edit distance
+    Fuzzy(String, usize),
+    /// `"a few words"` next to each other in one record, or a word the tokenizer split
+    Phrase(Vec<String>)
+}
+
+impl Index {
+    /// indexes the file names, hashes, keys and values of the entries
+    pub fn build(entries: &[Entry]) -> Self {
+        let mut index = Self {
+            version: INDEX_VERSION,
+            source: None,
+            records: Vec::new(),
+            words: BTreeMap::new(),
+            hashes: BTreeMap::new()
+        };
+        for entry in entries {
+            let words = tokenize(entry.file);
+            index.add(entry.file, "", words.iter().map(|x| x.as_str()), "");
+            let record = index.records.len() as u32 - 1;
+            for hash in entry.hashes.iter() {
+                index.hashes.entry(*hash).or_default().push(record);
+            }
+            for (key, value) in entry.fields.iter() {
+                index.add(entry.file, key, tokenize(key).iter().map(|x| x.as_str()), value);
+            }
+        }
+
+        index
+    }
+
+    fn add<'a>(&mut self, file: &str, key: &str, key_words: impl Iterator<Item = &'a str>, value: &str) {
+        let record = self.records.len() as u32;
+        self.records.push((file.to_owned(), key.to_owned()));
+        let mut position = 0;
+        for word in key_words {
+            self.words.entry(word.to_owned()).or_default().push((record, position));
+            position += 1;
+        }
+        position += FIELD_GAP;
+        for word in tokenize(value) {
+            self.words.entry(word).or_default().push((record, position));
+            position += 1;
+        }
+    }
+
+    /// the index stored next to the json when it is still up to date, otherwise a new one that is saved
+    /// there when indexes are persisted
+    pub fn load_or_build(json_path: &str, entries: &[Entry]) -> Self {
+        let index_path = Path::new(json_path).with_extension("index.json");
+        let source = Source::of(json_path);
+        let stored = utils::read_file(&index_path.display().to_string()).ok()
+            .and_then(|x| serde_json::from_slice::<Index>(&x).ok())
+            .filter(|x| x.version == INDEX_VERSION && source.is_some() && x.source == source);
+        if let Some(index) = stored {
+            return index;
+        }
+
+        let mut index = Index::build(entries);
+        index.source = source;
+        if persist() {
+            let _ = index.save(&index_path);
+        }
+
+        index
+    }
+
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string(self)?;
+        let mut f = File::create(path)?;
+        f.write_all(json.as_bytes())?;
+
+        Ok(())
+    }
+
+    /// the (file, key) records holding a term of the search, in files that hold every term
+    pub fn search(&self, text: &str) -> Vec<(&str, &str)> {
+        let found: Vec<HashSet<u32>> = query::split(text).iter()
+            .filter_map(|x| {
+                let hash = query::parse_term_hash(&x.trim_matches('"').trim_end_matches(['*', '~']).to_lowercase());
+                let hashed = hash.and_then(|x| self.hashes.get(&x)).into_iter().flatten().copied();
+                parse_term(x).map(|term| self.find(&term).into_iter().chain(hashed).collect())
+            })
+            .collect();
+        if found.is_empty() {
+            return Vec::new();
+        }
+
+        let files = found.iter()
+            .map(|records| records.iter().map(|x| self.records[*x as usize].0.as_str()).collect::<HashSet<_>>())
+            .reduce(|a, b| a.intersection(&b).copied().collect())
+            .unwrap_or_default();
+        let mut records: Vec<u32> = found.into_iter().flatten().collect::<HashSet<_>>().into_iter()
+            .filter(|x| files.contains(self.records[*x as usize].0.as_str()))
+            .collect();
+        records.sort_unstable();
+
+        records.into_iter().map(|x| (self.records[x as usize].0.as_str(), self.records[x as usize].1.as_str())).collect()
+    }
+
+    /// the files holding every term of the search
+    pub fn files(&self, text: &str) -> HashSet<String> {
+        self.search(text).into_iter().map(|x| x.0.to_owned()).collect()
+    }
+
+    fn find(&self, term: &Term) -> HashSet<u32> {
+        match term {
+            Term::Word(word) => self.records_of(|x| x.contains(word.as_str())),
+            Term::Prefix(prefix) => self.words.range(prefix.to_owned()..)
+                .take_while(|(word, _)| word.starts_with(prefix.as_str()))
+                .flat_map(|x| x.1.iter().map(|x| x.0))
+                .collect(),
+            Term::Fuzzy(word, distance) => self.records_of(|x| x.chars().count().abs_diff(word.chars().count()) <= *distance && edit_distance(x, word) <= *distance),
+            Term::Phrase(words) => {
+                // a substring of the text like without an index: the first word may end a longer word and the
+                // last may start one
+                let last = words.len() - 1;
+                let positions: Vec<HashSet<(u32, u32)>> = words.iter().enumerate()
+                    .map(|(i, word)| match i {
+                        0 => self.positions_of(|x| x.ends_with(word.as_str())),
+                        i if i == last => self.positions_of(|x| x.starts_with(word.as_str())),
+                        _ => self.words.get(word).into_iter().flatten().copied().collect()
+                    })
+                    .collect();
+                positions[0].iter()
+                    .filter(|(record, position)| positions.iter().enumerate().skip(1).all(|(i, x)| x.contains(&(*record, position + i as u32))))
+                    .map(|x| x.0)
+                    .collect()
+            }
+        }
+    }
+
+    fn records_of(&self, test: impl Fn(&str) -> bool) -> HashSet<u32> {
+        self.positions_of(test).into_iter().map(|x| x.0).collect()
+    }
+
+    fn positions_of(&self, test: impl Fn(&str) -> bool) -> HashSet<(u32, u32)> {
+        self.words.iter()
+            .filter(|(word, _)| test(word))
+            .flat_map(|x| x.1.iter().copied())
+            .collect()
+    }
+}
+
+/// the index of a loaded dataset with the files its last search found, as views ask every frame
+#[derive(Default)]
+pub struct SearchIndex {
+    index: Option<Index>,
+    last: Option<(String, HashSet<String>)>
+}
+
+impl SearchIndex {
+    pub fn new(index: Index) -> Self {
+        Self {
+            index: Some(index),
+            last: None
+        }
+    }
+
+    pub fn index(&self) -> Option<&Index> {
+        self.index.as_ref()
+    }
+
+    /// the files the query finds, or `None` when there is no index or the query cannot use one
+    pub fn files(&mut self, query: &Query) -> Option<&HashSet<String>> {
+        let (index, text) = (self.index.as_ref()?, query.index_text()?);
+        if self.last.as_ref().map(|x| x.0 != text).unwrap_or(true) {
+            self.last = Some((text.to_owned(), index.files(text)));
+        }
+
+        self.last.as_ref().map(|x| &x.1)
+    }
+}
+
+#[derive(Serialize)]
+struct SearchHit<'a> {
+    file: &'a str,
+    key: &'a str,
+    value: &'a str
+}
+
+/// searches an stl or aff json with its index and writes the records found to `search.json`
+pub fn run_search(extension: &str, json_path: &str, text: &str) -> io::Result<()> {
+    let parser: Box<dyn Parser> = match extension {
+        "stl" => Box::new(Stl::load(json_path)?),
+        "aff" => Box::new(Aff::load(json_path)?),
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("no index for .{} data", extension)))
+    };
+    let entries = parser.entries();
+    let index = Index::load_or_build(json_path, &entries);
+    let by_file: HashMap<&str, &Entry> = entries.iter().map(|x| (x.file, x)).collect();
+
+    let hits: Vec<SearchHit> = index.search(text).into_iter()
+        .map(|(file, key)| {
+            let value = by_file.get(file)
+                .and_then(|x| x.fields.iter().find(|field| field.0 == key))
+                .map(|x| x.1.as_ref())
+                .unwrap_or_default();
+            SearchHit { file, key, value }
+        })
+        .collect();
+
+    let json = serde_json::to_string_pretty(&hits)?;
+    let mut log = File::create("search.json")?;
+    log.write_all(json.as_bytes())?;
+
+    Ok(())
+}
+
+/// lowercase runs of letters and digits
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|x: char| !x.is_alphanumeric())
+        .filter(|x| !x.is_empty())
+        .map(|x| x.to_lowercase())
+        .collect()
+}
+
+fn parse_term(word: &str) -> Option<Term> {
+    if let Some(word) = word.strip_suffix('*') {
+        return tokenize(word).pop().map(Term::Prefix);
+    }
+    if let Some(word) = word.strip_suffix('~') {
+        return tokenize(word).pop().map(|x| {
+            let distance = match x.chars().count() {
+                0..=5 => 1,
+                _ => 2
+            };
+            Term::Fuzzy(x, distance)
+        });
+    }
+
+    let mut words = tokenize(word);
+    match words.len() {
+        0 => None,
+        1 => words.pop().map(Term::Word),
+        _ => Some(Term::Phrase(words))
+    }
+}
+
+/// levenshtein distance over characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous + (a != *b) as usize;
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(previous + 1).min(row[j] + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries() -> Vec<Entry<'static>> {
+        vec![
+            Entry::new("SpellNames.stl", vec![0x1234]).field("Fireball", "Hurls a ball of fire"),
+            Entry::new("ItemNames.stl", vec![42]).field("Name", "Damage of the sword"),
+            Entry::new("Affixes.aff", Vec::new()).field("Legendary", "Fire and ice ball")
+        ]
+    }
+
+    fn files(index: &Index, text: &str) -> Vec<String> {
+        let mut files: Vec<String> = index.files(text).into_iter().collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn words_match_inside_longer_words() {
+        let index = Index::build(&entries());
+        assert_eq!(files(&index, "fire"), ["Affixes.aff", "SpellNames.stl"]);
+        assert_eq!(files(&index, "reba"), ["SpellNames.stl"]);
+        assert_eq!(files(&index, "sword fire"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn prefixes_match_the_start_of_words() {
+        let index = Index::build(&entries());
+        assert_eq!(files(&index, "firebal*"), ["SpellNames.stl"]);
+        assert_eq!(files(&index, "ball*"), ["Affixes.aff", "SpellNames.stl"]);
+        assert_eq!(files(&index, "reball*"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn fuzzy_terms_allow_a_few_edits() {
+        let index = Index::build(&entries());
+        assert_eq!(files(&index, "damge~"), ["ItemNames.stl"]);
+        assert_eq!(files(&index, "swrod~"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn phrases_need_the_words_in_order() {
+        let index = Index::build(&entries());
+        assert_eq!(files(&index, "\"ball of fire\""), ["SpellNames.stl"]);
+        assert_eq!(files(&index, "\"ire and ic\""), ["Affixes.aff"]);
+        assert_eq!(files(&index, "\"fire ball\""), Vec::<String>::new());
+    }
+
+    #[test]
+    fn hashes_and_file_names_are_indexed() {
+        let index = Index::build(&entries());
+        assert_eq!(files(&index, "0x1234"), ["SpellNames.stl"]);
+        assert_eq!(files(&index, "42"), ["ItemNames.stl"]);
+        assert_eq!(files(&index, "itemnames"), ["ItemNames.stl"]);
+    }
+
+    #[test]
+    fn hashes_match_like_without_an_index() {
+        let entries = vec![
+            Entry::new("Coffee.stl", vec![0xCAFE]).field("Name", "espresso"),
+            Entry::new("Broken.stl", vec![0xBAD]),
+            Entry::new("Answer.stl", vec![42]),
+            Entry::new("Larger.stl", vec![142, 420, 0x42]),
+            Entry::new("Text.stl", Vec::new()).field("Name", "a bad cafe at 1420")
+        ];
+        let index = Index::build(&entries);
+        for text in ["cafe", "bad", "42", "0x42", "0xcafe", "51966", "\"bad cafe\"", "esp*"] {
+            let query = Query::parse(text, false, false).unwrap();
+            let mut expected: Vec<String> = entries.iter().filter(|x| query.matches(x)).map(|x| x.file.to_owned()).collect();
+            expected.sort();
+            assert_eq!(files(&index, text), expected, "{}", text);
+        }
+        assert_eq!(files(&index, "42"), ["Answer.stl", "Text.stl"]);
+        assert_eq!(files(&index, "cafe"), ["Text.stl"]);
+        assert_eq!(files(&index, "0xcafe"), ["Coffee.stl"]);
+    }
+
+    #[test]
+    fn stored_index_is_rebuilt_when_the_json_changes() {
+        let folder = std::env::temp_dir().join(format!("index_test_{}", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        let json = folder.join("data.json");
+        let json_path = json.display().to_string();
+        fs::write(&json, "[]").unwrap();
+
+        let index = Index::load_or_build(&json_path, &entries());
+        assert!(json.with_extension("index.json").exists());
+        assert_eq!(files(&index, "sword"), ["ItemNames.stl"]);
+
+        // the stored index is used while the json is unchanged, even for other entries
+        let index = Index::load_or_build(&json_path, &[]);
+        assert_eq!(files(&index, "sword"), ["ItemNames.stl"]);
+
+        fs::write(&json, "[{}]").unwrap();
+        let index = Index::load_or_build(&json_path, &[]);
+        assert!(files(&index, "sword").is_empty());
+
+        let _ = fs::remove_dir_all(&folder);
+    }
+}
@@ -0,0 +1,165 @@
+use std::{io, fs, path::Path, panic::{self, AssertUnwindSafe}, thread::{self, JoinHandle}, sync::{Arc, Mutex, atomic::{AtomicBool, AtomicUsize, Ordering}}};
+use egui::{Color32, ProgressBar};
+
+use crate::{game_build, sno::SnoReader, parsers::Parser};
+
+/// progress of a job, shared with its thread
+#[derive(Default)]
+struct Progress {
+    done: AtomicUsize,
+    /// files to read, 0 while the job cannot tell
+    total: AtomicUsize,
+    cancel: AtomicBool,
+    errors: Mutex<Vec<String>>
+}
+
+impl Progress {
+    fn error(&self, error: String) {
+        self.errors.lock().unwrap().push(error);
+    }
+}
+
+/// work running on its own thread so the ui keeps drawing, usually parsing a folder
+pub struct Job {
+    label: String,
+    progress: Arc<Progress>,
+    /// whether the job checks for cancel between files
+    cancellable: bool,
+    handle: Option<JoinHandle<Option<Box<dyn Parser>>>>
+}
+
+impl Job {
+    /// parses every file of the folder and writes its json like `Parser::run`, but a file that
+    /// fails is listed as an error and skipped instead of stopping the others
+    pub fn parse(label: String, mut parser: Box<dyn Parser>, path: String) -> Self {
+        let progress = Arc::new(Progress::default());
+        let shared = progress.clone();
+        let handle = thread::spawn(move || {
+            match Job::parse_folder(parser.as_mut(), &path, &shared) {
+                Ok(true) => Some(parser),
+                Ok(false) => None,
+                Err(e) => {
+                    shared.error(e.to_string());
+                    None
+                }
+            }
+        });
+
+        Self {
+            label,
+            progress,
+            cancellable: true,
+            handle: Some(handle)
+        }
+    }
+
+    /// runs work that does not report progress and gives no dataset back
+    pub fn spawn(label: String, work: impl FnOnce() -> io::Result<()> + Send + 'static) -> Self {
+        let progress = Arc::new(Progress::default());
+        let shared = progress.clone();
+        let handle = thread::spawn(move || {
+            if let Err(e) = work() {
+                shared.error(e.to_string());
+            }
+            None
+        });
+
+        Self {
+            label,
+            progress,
+            cancellable: false,
+            handle: Some(handle)
+        }
+    }
+
+    /// returns false when the job was cancelled before it was done
+    fn parse_folder(parser: &mut dyn Parser, path: &str, progress: &Progress) -> io::Result<bool> {
+        let build = game_build::detect(Path::new(path));
+        let files = fs::read_dir(path)?.collect::<io::Result<Vec<_>>>()?;
+        progress.total.store(files.len(), Ordering::Relaxed);
+
+        for file in files {
+            if progress.cancel.load(Ordering::Relaxed) {
+                return Ok(false);
+            }
+            let file_name = file.file_name().to_string_lossy().into_owned();
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                let mut f = SnoReader::open(&file.path())?;
+                parser.read_file(&file_name, &mut f, build)
+            }));
+            match result {
+                Ok(Ok(())) => (),
+                Ok(Err(e)) => progress.error(format!("{}: {}", file_name, e)),
+                Err(_) => progress.error(format!("{}: the parser panicked", file_name))
+            }
+            progress.done.fetch_add(1, Ordering::Relaxed);
+        }
+        parser.finish()?;
+
+        Ok(true)
+    }
+
+    /// whether the thread has not been joined yet, it may already be done until `take` joins it
+    pub fn is_running(&self) -> bool {
+        self.handle.is_some()
+    }
+
+    /// joins the thread once it is done and gives out the parsed dataset, only once
+    pub fn take(&mut self) -> Option<Box<dyn Parser>> {
+        match self.handle.as_ref().map(|x| x.is_finished()) {
+            Some(true) => self.handle.take().and_then(|x| match x.join() {
+                Ok(parser) => parser,
+                Err(_) => {
+                    self.progress.error("the job panicked".to_owned());
+                    None
+                }
+            }),
+            _ => None
+        }
+    }
+
+    /// shows the progress with a cancel button while running and the errors, returns true when the job
+    /// is done and was dismissed; jobs stay listed until then
+    pub fn show(&self, ui: &mut egui::Ui) -> bool {
+        let mut dismissed = false;
+        let done = self.progress.done.load(Ordering::Relaxed);
+        let total = self.progress.total.load(Ordering::Relaxed);
+        let cancelled = self.progress.cancel.load(Ordering::Relaxed);
+        ui.horizontal(|h| {
+            h.strong(&self.label);
+            if self.is_running() {
+                let progress = match total {
+                    0 => ProgressBar::new(0f32).animate(true),
+                    _ => ProgressBar::new(done as f32/total as f32).text(format!("{} / {}", done, total))
+                };
+                h.add(progress.desired_width(240f32));
+                if self.cancellable && !cancelled && h.button("cancel").clicked() {
+                    self.progress.cancel.store(true, Ordering::Relaxed);
+                }
+                if cancelled {
+                    h.label("cancelling");
+                }
+            }
+            else {
+                h.label(match cancelled {
+                    true => "cancelled".to_owned(),
+                    false => format!("done, {} files", done)
+                });
+                if h.button("dismiss").clicked() {
+                    dismissed = true;
+                }
+            }
+        });
+
+        let errors = self.progress.errors.lock().unwrap();
+        if !errors.is_empty() {
+            ui.collapsing(format!("{} errors", errors.len()), |ui| {
+                for error in errors.iter() {
+                    ui.colored_label(Color32::RED, error);
+                }
+            });
+        }
+
+        dismissed
+    }
+}
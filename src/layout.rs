@@ -0,0 +1,394 @@
+use std::{io::{self, Read, Seek}, collections::HashMap, path::Path};
+use itertools::Itertools;
+use serde::{Serialize, Deserialize};
+use serde_json::{Map, Value};
+
+use crate::{utils, sno::SnoReader};
+
+/// definitions file read from the working directory when no other one is given
+pub const LAYOUTS_FILE: &str = "layouts.toml";
+
+/// sno layouts described in a definitions file, read by a generic interpreter instead of a hand written parser
+#[derive(Serialize, Deserialize, Default)]
+pub struct Layouts {
+    #[serde(default)]
+    pub layouts: Vec<Layout>
+}
+
+impl Layouts {
+    /// reads a `.toml` or `.json` definitions file
+    pub fn read(path: &str) -> io::Result<Self> {
+        let buf = utils::read_file(path)?;
+        let text = String::from_utf8(buf).map_err(|x| invalid(&x.to_string()))?;
+
+        match Path::new(path).extension().and_then(|x| x.to_str()) {
+            Some("json") => Ok(serde_json::from_str(&text)?),
+            _ => toml::from_str(&text).map_err(|x| invalid(&format!("{}: {}", path, x)))
+        }
+    }
+
+    /// the definitions in `layouts.toml`, or none if there is no such file
+    pub fn load() -> io::Result<Self> {
+        Layouts::read_or_default(LAYOUTS_FILE)
+    }
+
+    /// a file that is not there has no definitions, one that cannot be parsed is an error
+    fn read_or_default(path: &str) -> io::Result<Self> {
+        match Layouts::read(path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Layouts::default()),
+            result => result
+        }
+    }
+
+    /// every layout of an extension, for any build
+    pub fn for_extension(&self, extension: &str) -> Vec<Layout> {
+        self.layouts.iter().filter(|x| x.extension == extension).cloned().collect()
+    }
+
+    pub fn extensions(&self) -> Vec<String> {
+        self.layouts.iter().map(|x| x.extension.to_owned()).unique().collect()
+    }
+}
+
+/// the layout covering a build, preferring the one with the latest `min_build`; when the build is
+/// unknown the newest layout is used
+pub fn for_build(layouts: &[Layout], build: Option<u32>) -> Option<&Layout> {
+    layouts.iter()
+        .filter(|x| match build {
+            Some(build) => x.min_build.unwrap_or(0) <= build && x.max_build.map(|max| build <= max).unwrap_or(true),
+            None => true
+        })
+        .max_by_key(|x| x.min_build.unwrap_or(0))
+}
+
+/// the fields of one sno format, starting at the beginning of the file, plus the named structs they refer to
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Layout {
+    pub extension: String,
+    /// first game build the layout applies to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub min_build: Option<u32>,
+    /// last game build the layout applies to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub max_build: Option<u32>,
+    pub fields: Vec<Field>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    #[serde(default)]
+    pub structs: HashMap<String, Vec<Field>>
+}
+
+/// a field is read at `offset` from the start of its struct, or right after the previous field
+///
+/// unnamed fields are read but left out of the output, so they can be used for padding
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Field {
+    #[serde(default)]
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub offset: Option<u32>,
+    #[serde(flatten)]
+    pub kind: FieldKind
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FieldKind {
+    U8,
+    U16,
+    U32,
+    I32,
+    U64,
+    F32,
+    Padding { size: u32 },
+    /// fixed size string stored inline, with trailing nulls removed
+    String { size: u32 },
+    /// inline struct from `structs`
+    Struct { of: String },
+    /// `count` consecutive elements, either a primitive type name or a struct name
+    Array { of: String, count: u32 },
+    /// offset and byte length pair pointing to a string (`of = "string"`) or to an array of elements,
+    /// `stride` overrides the element size when records are padded
+    Ref {
+        of: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(default)]
+        stride: Option<u32>
+    }
+}
+
+impl Layout {
+    /// reads a whole file into a json value following the layout
+    pub fn read(&self, f: &mut SnoReader) -> io::Result<Value> {
+        self.check()?;
+        utils::go_to(f, 0)?;
+        self.read_struct(&self.fields, f)
+    }
+
+    /// measures the fields and every struct once, so a struct containing itself is an error before
+    /// reading descends into it
+    fn check(&self) -> io::Result<()> {
+        self.struct_size(&self.fields)?;
+        for name in self.structs.keys() {
+            self.element_size(name, &mut Vec::new())?;
+        }
+
+        Ok(())
+    }
+
+    fn read_struct(&self, fields: &[Field], f: &mut SnoReader) -> io::Result<Value> {
+        let start = f.stream_position()?;
+        let mut values = Map::new();
+
+        for field in fields {
+            if let Some(offset) = field.offset {
+                utils::go_to(f, start + offset as u64)?;
+            }
+            let value = self.read_field(&field.kind, f)?;
+            if !field.name.is_empty() {
+                values.insert(field.name.to_owned(), value);
+            }
+        }
+        utils::go_to(f, start + self.struct_size(fields)? as u64)?;
+
+        Ok(Value::Object(values))
+    }
+
+    fn read_field(&self, kind: &FieldKind, f: &mut SnoReader) -> io::Result<Value> {
+        let value = match kind {
+            FieldKind::U8 => Value::from(read_bytes(f, 1)?[0]),
+            FieldKind::U16 => {
+                let buf = read_bytes(f, 2)?;
+                Value::from(u16::from_le_bytes([buf[0], buf[1]]))
+            },
+            FieldKind::U32 => Value::from(utils::read_u32(f)?),
+            FieldKind::I32 => Value::from(utils::read_u32(f)? as i32),
+            FieldKind::U64 => {
+                let low = utils::read_u32(f)? as u64;
+                let high = utils::read_u32(f)? as u64;
+                Value::from(high << 32 | low)
+            },
+            FieldKind::F32 => Value::from(utils::read_f32(f)?),
+            FieldKind::Padding { size } => {
+                utils::padding(f, *size as i64)?;
+                Value::Null
+            },
+            FieldKind::String { size } => Value::from(text(&read_bytes(f, *size)?)),
+            FieldKind::Struct { of } => self.read_struct(self.get_struct(of)?, f)?,
+            FieldKind::Array { of, count } => {
+                let mut values = Vec::new();
+                for _ in 0..*count {
+                    values.push(self.read_element(of, f)?);
+                }
+                Value::Array(values)
+            },
+            FieldKind::Ref { of, stride } => {
+                let offset = utils::read_ptr(f)?;
+                let len = utils::read_u32(f)?;
+                self.read_ref(f, of, *stride, offset, len)?
+            }
+        };
+
+        Ok(value)
+    }
+
+    fn read_ref(&self, f: &mut SnoReader, of: &str, stride: Option<u32>, offset: u32, len: u32) -> io::Result<Value> {
+        if of == "string" {
+            let buf = utils::read_offset(f, offset as u64, len as usize)?;
+            return Ok(Value::from(text(&buf)));
+        }
+
+        let stride = match stride {
+            Some(stride) => stride,
+            None => self.element_size(of, &mut Vec::new())?
+        };
+        if stride == 0 {
+            return Err(invalid(&format!("layout element '{}' has no size", of)));
+        }
+
+        let old_pos = f.stream_position()?;
+        let mut values = Vec::new();
+        for i in 0..len/stride {
+            utils::go_to(f, utils::record_pos(offset, i, stride)?)?;
+            values.push(self.read_element(of, f)?);
+        }
+        utils::go_to(f, old_pos)?;
+
+        Ok(Value::Array(values))
+    }
+
+    fn read_element(&self, of: &str, f: &mut SnoReader) -> io::Result<Value> {
+        match self.structs.get(of) {
+            Some(fields) => self.read_struct(fields, f),
+            None => self.read_field(&primitive(of)?, f)
+        }
+    }
+
+    fn get_struct(&self, name: &str) -> io::Result<&Vec<Field>> {
+        self.structs.get(name).ok_or_else(|| invalid(&format!("layout struct '{}' is not defined", name)))
+    }
+
+    /// bytes from the start of a struct to the end of its furthest field
+    pub fn struct_size(&self, fields: &[Field]) -> io::Result<u32> {
+        self.fields_size(fields, &mut Vec::new())
+    }
+
+    /// `visiting` holds the structs being measured around these fields, meeting one again is a cycle
+    fn fields_size(&self, fields: &[Field], visiting: &mut Vec<String>) -> io::Result<u32> {
+        let mut pos = 0u32;
+        let mut size = 0;
+        for field in fields {
+            pos = field.offset.unwrap_or(pos).checked_add(self.kind_size(&field.kind, visiting)?)
+                .ok_or_else(|| invalid(&format!("layout field '{}' ends past 4 GB", field.name)))?;
+            size = size.max(pos);
+        }
+
+        Ok(size)
+    }
+
+    fn kind_size(&self, kind: &FieldKind, visiting: &mut Vec<String>) -> io::Result<u32> {
+        let size = match kind {
+            FieldKind::U8 => 1,
+            FieldKind::U16 => 2,
+            FieldKind::U32 | FieldKind::I32 | FieldKind::F32 => 4,
+            FieldKind::U64 | FieldKind::Ref { .. } => 8,
+            FieldKind::Padding { size } | FieldKind::String { size } => *size,
+            FieldKind::Struct { of } => self.element_size(of, visiting)?,
+            FieldKind::Array { of, count } => self.element_size(of, visiting)?.checked_mul(*count)
+                .ok_or_else(|| invalid(&format!("layout array of '{}' is larger than 4 GB", of)))?
+        };
+
+        Ok(size)
+    }
+
+    fn element_size(&self, of: &str, visiting: &mut Vec<String>) -> io::Result<u32> {
+        match self.structs.get(of) {
+            Some(_) if visiting.iter().any(|x| x == of) => Err(invalid(&format!("layout struct '{}' contains itself", of))),
+            Some(fields) => {
+                visiting.push(of.to_owned());
+                let size = self.fields_size(fields, visiting);
+                visiting.pop();
+                size
+            },
+            None => self.kind_size(&primitive(of)?, visiting)
+        }
+    }
+}
+
+/// element types usable by name in `of`
+fn primitive(name: &str) -> io::Result<FieldKind> {
+    match name {
+        "u8" => Ok(FieldKind::U8),
+        "u16" => Ok(FieldKind::U16),
+        "u32" => Ok(FieldKind::U32),
+        "i32" => Ok(FieldKind::I32),
+        "u64" => Ok(FieldKind::U64),
+        "f32" => Ok(FieldKind::F32),
+        _ => Err(invalid(&format!("unknown layout type '{}'", name)))
+    }
+}
+
+fn read_bytes(f: &mut SnoReader, len: u32) -> io::Result<Vec<u8>> {
+    let mut buf = vec![0u8; len as usize];
+    f.read_exact(&mut buf)?;
+
+    Ok(buf)
+}
+
+fn text(buf: &[u8]) -> String {
+    String::from_utf8_lossy(buf).replace(char::from(0), "")
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(text: &str) -> Layout {
+        let layouts: Layouts = toml::from_str(text).unwrap();
+        layouts.layouts.into_iter().next().unwrap()
+    }
+
+    #[test]
+    fn struct_sizes_follow_offsets_and_nesting() {
+        let layout = layout(r#"
+            [[layouts]]
+            extension = "test"
+            [[layouts.fields]]
+            name = "pairs"
+            type = "array"
+            of = "pair"
+            count = 3
+            [[layouts.structs.pair]]
+            name = "key"
+            type = "ref"
+            of = "string"
+            offset = 0x8
+            [[layouts.structs.pair]]
+            type = "padding"
+            size = 4
+        "#);
+        assert_eq!(layout.struct_size(&layout.structs["pair"]).unwrap(), 20);
+        assert_eq!(layout.struct_size(&layout.fields).unwrap(), 60);
+    }
+
+    #[test]
+    fn struct_cycles_are_errors() {
+        let layout = layout(r#"
+            [[layouts]]
+            extension = "test"
+            [[layouts.fields]]
+            name = "a"
+            type = "struct"
+            of = "a"
+            [[layouts.structs.a]]
+            name = "b"
+            type = "array"
+            of = "b"
+            count = 2
+            [[layouts.structs.b]]
+            name = "a"
+            type = "struct"
+            of = "a"
+        "#);
+        let error = layout.struct_size(&layout.fields).unwrap_err();
+        assert!(error.to_string().contains("contains itself"), "{}", error);
+
+        let error = layout.read(&mut SnoReader::new(vec![0u8; 0x40], None)).unwrap_err();
+        assert!(error.to_string().contains("contains itself"), "{}", error);
+    }
+
+    #[test]
+    fn unused_struct_cycles_are_errors() {
+        let layout = layout(r#"
+            [[layouts]]
+            extension = "test"
+            [[layouts.fields]]
+            name = "id"
+            type = "u32"
+            [[layouts.structs.a]]
+            name = "a"
+            type = "struct"
+            of = "a"
+        "#);
+        assert!(layout.read(&mut SnoReader::new(vec![0u8; 0x40], None)).is_err());
+    }
+
+    #[test]
+    fn missing_files_are_empty_and_broken_ones_errors() {
+        let folder = std::env::temp_dir().join(format!("layout_test_{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        let path = folder.join("layouts.toml").display().to_string();
+        assert!(Layouts::read_or_default(&path).unwrap().layouts.is_empty());
+
+        std::fs::write(&path, "[[layouts]]\nextension = ").unwrap();
+        assert!(Layouts::read_or_default(&path).is_err());
+
+        let _ = std::fs::remove_dir_all(&folder);
+    }
+}
@@ -1,14 +1,44 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
-use std::{io::{self}, env, fs};
-use parsers::{stl::Stl, aff::Aff, skl::Skl};
+use std::{io::{self, Write}, env, fs::{self, File}};
+use parsers::{Parser, aspect::Aspects, generic::Generic, stl::Stl};
+use toc::CoreToc;
+use layout::Layouts;
 
 mod utils;
+mod sno;
 mod parsers;
 mod app;
+mod toc;
+mod casc;
+mod blte;
+mod layout;
+mod game_build;
+mod raw;
+mod probe;
+mod hex;
+mod translation;
+mod skill_tree;
+mod planner;
+mod skill_graph;
+mod skill_lint;
+mod query;
+mod results;
+mod index;
+mod job;
+mod table;
 
 fn main() -> io::Result<()> {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+    if let Some(i) = args.iter().position(|x| x == "--build") {
+        let build = args.get(i + 1).and_then(|x| x.parse::<u32>().ok()).expect("usage: --build <build number>");
+        game_build::set_override(build);
+        args.drain(i..i + 2);
+    }
+    if let Some(i) = args.iter().position(|x| x == "--raw") {
+        raw::set_enabled(true);
+        args.remove(i);
+    }
     let gui_mode = match args.len() {
         1 => true,
         _ => false // for now, assume more than default arguments is not gui mode
@@ -18,8 +48,67 @@ fn main() -> io::Result<()> {
         run_gui();
     }
     else {
-        let path = &args[1];
-        run_cli(path.to_string())?;
+        match args[1].as_str() {
+            "aspects" => {
+                let path = args.get(2).expect("usage: aspects <aff folder>");
+                let mut aspects = Aspects::new();
+                aspects.run(path.to_string())?;
+            },
+            "toc" => {
+                let usage = "usage: toc <CoreTOC.dat> <folder>";
+                let toc_path = args.get(2).expect(usage);
+                let path = args.get(3).expect(usage);
+                run_toc(toc_path, path.to_string())?;
+            },
+            "casc" => {
+                let usage = "usage: casc <game folder> <output folder>";
+                let install_path = args.get(2).expect(usage);
+                let out_path = args.get(3).expect(usage);
+                casc::run(install_path, out_path)?;
+            },
+            "blte" => {
+                let usage = "usage: blte <blob folder> <output folder> [CoreTOC.dat]";
+                let path = args.get(2).expect(usage);
+                let out_path = args.get(3).expect(usage);
+                blte::run(path, out_path, args.get(4).map(|x| x.as_str()))?;
+            },
+            "layout" => {
+                let usage = "usage: layout <layouts file> <folder>";
+                let layouts_path = args.get(2).expect(usage);
+                let path = args.get(3).expect(usage);
+                run_layout(layouts_path, path.to_string())?;
+            },
+            "probe" => {
+                let path = args.get(2).expect("usage: probe <folder>");
+                probe::run(path)?;
+            },
+            "stl-write" => {
+                let usage = "usage: stl-write <stl json> <output folder> [original stl folder]";
+                let json_path = args.get(2).expect(usage);
+                let out_path = args.get(3).expect(usage);
+                Stl::load(json_path)?.write(out_path, args.get(4).map(|x| x.as_str()))?;
+            },
+            "stl-export" => {
+                let usage = "usage: stl-export <stl json> <.po or .xliff file>";
+                let json_path = args.get(2).expect(usage);
+                let path = args.get(3).expect(usage);
+                translation::export(&Stl::load(json_path)?, path)?;
+            },
+            "stl-import" => {
+                let usage = "usage: stl-import <stl json> <.po or .xliff file> [output json, default <name>.translated.json]";
+                let json_path = args.get(2).expect(usage);
+                let path = args.get(3).expect(usage);
+                translation::run_import(json_path, path, args.get(4).map(|x| x.as_str()))?;
+            },
+            "search" => {
+                let usage = "usage: search <stl or aff> <json> <search>";
+                let extension = args.get(2).expect(usage);
+                let json_path = args.get(3).expect(usage);
+                let text = args.get(4..).filter(|x| !x.is_empty()).expect(usage).join(" ");
+                index::run_search(extension, json_path, &text)?;
+            },
+            path => run_cli(path.to_string())?
+        }
     }
 
     Ok(())
@@ -44,27 +133,62 @@ fn native() -> eframe::Result<()> {
     )
 }
 
+/// picks the parser from the extension of the files in a folder
+fn folder_parser(path: &str) -> io::Result<Option<Box<dyn Parser>>> {
+    parsers::from_extension(&folder_extension(path)?)
+}
+
+/// the extension of the files in a folder, read from its last file
+fn folder_extension(path: &str) -> io::Result<String> {
+    let last_file = fs::read_dir(path)?.last()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("{} is empty", path)))??
+        .path();
+
+    last_file.extension()
+        .and_then(|x| x.to_str())
+        .map(|x| x.to_owned())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("{} has no extension", last_file.display())))
+}
+
 fn run_cli(path: String) -> io::Result<()> {
-    let p = path.as_str();
-    let dir = fs::read_dir(p)?;
-    let last_file =  dir.last().unwrap()?.path();
-    let extension = last_file.extension().unwrap();
-
-    match extension.to_str() {
-        Some("stl") => {
-            let mut stl = Stl::new();
-            let _ = stl.run(path);
-        },
-        Some("aff") => {
-            let mut aff = Aff::new();
-            let _ = aff.run(path);
+    match folder_parser(&path)? {
+        Some(mut parser) => {
+            let _ = parser.run(path);
         },
-        Some("skl") => {
-            let mut skl = Skl::new();
-            let _ = skl.run(path);
-        }
         _ => todo!("Not yet implemented")
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// parses a folder and matches every record against the game's sno table of contents
+fn run_toc(toc_path: &str, path: String) -> io::Result<()> {
+    let toc = CoreToc::read(toc_path)?;
+    let mut parser = match folder_parser(&path)? {
+        Some(parser) => parser,
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("no parser for the files in {}", path)))
+    };
+    parser.run(path)?;
+
+    let json = serde_json::to_string_pretty(&toc.report(&parser.hash_ids()))?;
+    let mut log = File::create("toc.json")?;
+    log.write_all(json.as_bytes())?;
+
+    let json = serde_json::to_string_pretty(&toc)?;
+    let mut log = File::create("coretoc.json")?;
+    log.write_all(json.as_bytes())?;
+
+    Ok(())
+}
+
+/// parses a folder with the layout for its extension from a definitions file, even if a built in parser exists
+fn run_layout(layouts_path: &str, path: String) -> io::Result<()> {
+    let layouts = Layouts::read(layouts_path)?;
+    let extension = folder_extension(&path)?;
+
+    let layouts = layouts.for_extension(&extension);
+    match layouts.is_empty() {
+        true => Err(io::Error::new(io::ErrorKind::NotFound, format!("{} has no layout for .{}", layouts_path, extension))),
+        false => Generic::new(&extension, layouts).run(path)
+    }
+}
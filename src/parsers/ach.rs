@@ -0,0 +1,214 @@
+use std::{io::{self, Write}, fs::File, collections::HashMap};
+use itertools::Itertools;
+use serde::{Serialize, Deserialize};
+use serde_json;
+
+use super::{self as parsers, Parser, Reveal, stl::{Stl, ListRef, NAME_KEY, DESCRIPTION_KEY}};
+use crate::{utils, game_build, raw::{self, RawRegion}, sno::SnoReader, query::{Query, Entry}, index::Index};
+
+#[derive(Serialize, Deserialize)]
+pub struct Ach {
+    pub files: HashMap<String, AchFile>
+}
+
+impl Ach {
+    pub fn new() -> Self {
+        Self {
+            files: HashMap::new()
+        }
+    }
+
+    /// returns the achievement without criteria, plus offset and length of the criteria block
+    fn header(f: &mut SnoReader) -> io::Result<(AchFile, u32, u32)> {
+        let _deadbeef = utils::read_u32(f)?;
+        let _file_type = utils::read_u32(f)?;
+        utils::padding(f, 4)?;
+        let hash_id = utils::read_u32(f)?;
+        utils::padding(f, 16)?; // end first line
+
+        let category = utils::read_u32(f)?;
+        let points = utils::read_u32(f)?;
+        let string_list_id = utils::read_u32(f)?;
+        utils::padding(f, 20)?; // end second line
+
+        let criteria_offset = utils::read_ptr(f)?;
+        let criteria_len = utils::read_u32(f)?;
+        utils::padding(f, 8)?;
+
+        Ok((AchFile::new(hash_id, category, points, string_list_id), criteria_offset, criteria_len))
+    }
+
+    fn criterion(f: &mut SnoReader) -> io::Result<AchCriterion> {
+        let id = utils::read_u32(f)?;
+        let criterion_type = utils::read_u32(f)?;
+        let required = utils::read_u32(f)?;
+        let param = utils::read_u32(f)?;
+        utils::padding(f, 8)?;
+
+        Ok(AchCriterion { id, criterion_type, required, param })
+    }
+
+    /// fills in achievement names and descriptions from a previously parsed string list dump
+    pub fn resolve_strings(&mut self, stl: &Stl) {
+        let ids = stl.ids();
+        for (file_name, file) in self.files.iter_mut() {
+            if let Some(strings) = file.string_list.resolve(file_name, stl, &ids) {
+                file.name = strings.fields.get(NAME_KEY).cloned();
+                file.description = strings.fields.get(DESCRIPTION_KEY).cloned();
+            }
+        }
+    }
+
+    /// the file as a search entry, with its name and description
+    fn entry<'a>(file_name: &'a str, file: &'a AchFile) -> Entry<'a> {
+        Entry::new(file_name, vec![file.hash_id])
+            .field_opt("name", file.name.as_ref())
+            .field_opt("description", file.description.as_ref())
+            .field("category", file.category.to_string())
+            .field("points", file.points.to_string())
+    }
+}
+
+impl Parser for Ach {
+    fn run(&mut self, path: String) -> io::Result<()> {
+        parsers::read_folder(self, &path)?;
+        self.finish()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if let Ok(stl) = Stl::load("stl.json") {
+            self.resolve_strings(&stl);
+        }
+
+        let json = serde_json::to_string_pretty(&self)?;
+        let mut log = File::create("ach.json")?;
+        log.write_all(json.as_bytes())?;
+        raw::write_report("ach", self.files.values().map(|x| &x.raw))?;
+
+        Ok(())
+    }
+
+    fn read_file(&mut self, file_name: &str, f: &mut SnoReader, build: Option<u32>) -> io::Result<()> {
+        let records = game_build::records(build);
+        let (mut ach_file, criteria_offset, criteria_len) = Ach::header(f)?;
+
+        let num_criteria = criteria_len/records.ach_criterion;
+        for i in 0..num_criteria {
+            utils::go_to(f, utils::record_pos(criteria_offset, i, records.ach_criterion)?)?;
+            f.begin("criterion");
+            ach_file.criteria.push(Ach::criterion(f)?);
+        }
+
+        ach_file.raw = f.take_raw();
+        self.files.insert(file_name.to_owned(), ach_file);
+
+        Ok(())
+    }
+
+    fn data_view(&mut self, ui: &mut egui::Ui, query: &Query, reveal: Option<&Reveal>) {
+        let files = &self.files;
+        egui::Grid::new("ach_grid")
+            .show(ui, |ui| {
+                for file_key in files.keys().filter(|x| query.matches(&Ach::entry(x, &files[*x]))).sorted() {
+                    let achievement = &files[file_key];
+                    parsers::file_header(ui, file_key, reveal.is_some_and(|x| x.is_file(file_key)), |ui| {
+                        ui.horizontal(|h| {
+                            h.strong("hash_id:");
+                            h.label(format!("{} ({:X})", achievement.hash_id, achievement.hash_id));
+                        });
+                        ui.horizontal(|h| {
+                            h.strong("name:");
+                            h.label(achievement.name.as_deref().unwrap_or("<missing>"));
+                        });
+                        ui.horizontal(|h| {
+                            h.strong("description:");
+                            h.label(achievement.description.as_deref().unwrap_or("<missing>"));
+                        });
+                        ui.horizontal(|h| {
+                            h.strong("category:");
+                            h.label(format!("{}", achievement.category));
+                            h.strong("points:");
+                            h.label(format!("{}", achievement.points));
+                        });
+                        ui.collapsing("criteria", |ui| {
+                            for criterion in achievement.criteria.iter() {
+                                ui.label(format!("{}: type {}, {} x {:X}", criterion.id, criterion.criterion_type, criterion.required, criterion.param));
+                            }
+                        });
+                    });
+                    ui.end_row();
+                }
+            });
+    }
+
+    fn load_data_file(&self) -> Option<Box<dyn Parser>> {
+        match utils::load_or_pick_data_file("ach.json") {
+            Some(buf) => {
+                let data_str = String::from_utf8(buf).unwrap();
+                let ach: Ach = serde_json::from_str(&data_str).unwrap();
+                Some(Box::new(ach) as Box<dyn Parser>)
+            },
+            _ => None
+        }
+    }
+
+    fn tab_title(&self) -> String {
+        ".ach".to_owned()
+    }
+
+    fn hash_ids(&self) -> Vec<(String, u32)> {
+        self.files.iter().map(|(k, v)| (k.to_owned(), v.hash_id)).collect()
+    }
+
+    fn entries(&self) -> Vec<Entry<'_>> {
+        self.files.iter().map(|(k, v)| Ach::entry(k, v)).collect()
+    }
+
+    fn index(&self) -> Option<&Index> {
+        None
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AchFile {
+    pub hash_id: u32,
+    pub category: u32,
+    pub points: u32,
+    #[serde(flatten)]
+    pub string_list: ListRef,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub criteria: Vec<AchCriterion>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub raw: Vec<RawRegion>
+}
+
+impl AchFile {
+    fn new(hash_id: u32, category: u32, points: u32, string_list_id: u32) -> Self {
+        Self {
+            hash_id,
+            category,
+            points,
+            string_list: ListRef::new(string_list_id),
+            name: None,
+            description: None,
+            criteria: Vec::new(),
+            raw: Vec::new()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AchCriterion {
+    pub id: u32,
+    pub criterion_type: u32,
+    pub required: u32,
+    pub param: u32
+}
@@ -1,23 +1,42 @@
-use std::{io::{self, Write}, fs::{File, self}, collections::HashMap};
+use std::{io::{self, Write}, fs::File, collections::HashMap, sync::Arc};
 use itertools::Itertools;
 use serde::{Serialize, Deserialize};
 use serde_json;
 
-use super::{Parser};
-use crate::utils;
+use super::{self as parsers, Parser, Reveal};
+use crate::{utils, game_build, raw::{self, RawRegion}, sno::SnoReader, query::{Query, Entry}, index::{Index, SearchIndex}, table::{TableView, Row}};
+
+/// the key reference of a value follows 20 bytes in, on the next 8 byte boundary from the first value, so
+/// with 44 byte values it is 24 bytes into even values and 20 into odd ones
+const KEY_OFFSET: u32 = 20;
+const KEY_ALIGNMENT: u32 = 8;
 
 #[derive(Serialize, Deserialize)]
 pub struct Aff {
-    pub files: HashMap<String, AffFile>
+    pub files: HashMap<String, AffFile>,
+    #[serde(skip)]
+    search: SearchIndex,
+    #[serde(skip)]
+    table: TableView
 }
 
 impl Aff {
     pub fn new() -> Self {
         Self {
-            files: HashMap::new()
+            files: HashMap::new(),
+            search: SearchIndex::default(),
+            table: TableView::default()
         }
     }
 
+    /// loads a previously written aff json dump without prompting
+    pub fn load(path: &str) -> io::Result<Self> {
+        let buf = utils::read_file(path)?;
+        let aff = serde_json::from_slice(&buf)?;
+
+        Ok(aff)
+    }
+
     fn new_file(&mut self, file_name: &str, hash_id: u32) {
         self.files.insert(file_name.to_owned(), AffFile::new(hash_id));
     }
@@ -29,7 +48,8 @@ impl Aff {
             });
     }
     
-    fn header(f: &mut File) -> io::Result<(u32, u32, u32)> {
+    /// returns offset and length of the value table and hash_id, the bytes from 0x10 to 0x90 are skipped
+    pub fn header(f: &mut SnoReader) -> io::Result<(u32, u32, u32)> {
         let _deadbeef = utils::read_u32(f)?;
         let _file_type = utils::read_u32(f)?;
         utils::padding(f, 4)?;
@@ -37,74 +57,138 @@ impl Aff {
         let hash_id = utils::read_u32(f)?;
         utils::padding(f, 128)?;
 
-        let info_offset = utils::read_u32(f)? + 0x10u32;
+        let info_offset = utils::read_ptr(f)?;
         let info_len = utils::read_u32(f)?;
         utils::padding(f, 104)?;
 
         Ok((info_offset, info_len, hash_id))
     }
 
-    fn info(f: &mut File) -> io::Result<String> {
-        let key_offset = utils::read_u32(f)? + 0x10u32;
+    fn info(f: &mut SnoReader) -> io::Result<String> {
+        let key_offset = utils::read_ptr(f)?;
         let key_len = utils::read_u32(f)?;
         let buf = utils::read_offset(f, key_offset as u64, key_len as usize)?;
         let key_string = String::from_utf8(buf).unwrap();
 
         utils::padding(f, 8)?;
 
-        let _something_offset = utils::read_u32(f)? + 0x10u32;
-        let _something_len = utils::read_u32(f)?;
+        let _something_offset = utils::read_unknown(f)?;
+        let _something_len = utils::read_unknown(f)?;
 
         Ok(key_string)
     }
 
-    pub fn run(&mut self, path: String) -> io::Result<()> {
-        let dir = fs::read_dir(path)?;
+    /// the files the search finds, sorted, answered by the index when it has the search
+    fn found_keys<'a>(files: &'a HashMap<String, AffFile>, search: &mut SearchIndex, query: &Query) -> Vec<&'a String> {
+        let found = search.files(query);
+        files.keys()
+            .filter(|x| match found {
+                Some(found) => found.contains(*x),
+                None => query.matches(&Aff::entry(x, &files[*x]))
+            })
+            .sorted()
+            .collect()
+    }
 
-        for file in dir {
-            let f_u = file?;
-            let file_name = f_u.file_name().to_str().unwrap().to_owned();
+    /// a row for every value of the files, keyed by its position in the file
+    fn table_rows(files: &HashMap<String, AffFile>, keys: &[&String]) -> Vec<Row> {
+        let mut rows = Vec::new();
+        for file_key in keys {
+            let file = &files[*file_key];
+            let file_name: Arc<str> = Arc::from(file_key.as_str());
+            for (i, value) in file.values.iter().enumerate() {
+                rows.push(Row {
+                    file: file_name.clone(),
+                    hash_id: file.hash_id,
+                    key: i.to_string(),
+                    value: value.to_owned()
+                });
+            }
+        }
 
-            let mut f = File::open(f_u.path())?;
-            let (info_offset, info_len, hash_id) = Aff::header(&mut f)?;
-            self.new_file(file_name.as_str(), hash_id);
+        rows
+    }
 
-            utils::go_to(&mut f, info_offset.into())?; // move to info_offset
+    /// the file as a search entry, with its values
+    fn entry<'a>(file_name: &'a str, file: &'a AffFile) -> Entry<'a> {
+        file.values.iter().fold(Entry::new(file_name, vec![file.hash_id]), |entry, x| entry.field("value", x.as_str()))
+    }
+}
 
-            let num_pairs = info_len/44;
-            for i in 0..num_pairs {
-                match i % 2 {
-                    0 => utils::padding(&mut f, 24)?,
-                    _ => utils::padding(&mut f, 16)?
-                }
-                let value = Aff::info(&mut f)?;
-                self.add_field(&file_name, value.replace(char::from(0), ""));
-            }
-        }
+fn align(pos: u32) -> Option<u32> {
+    pos.checked_next_multiple_of(KEY_ALIGNMENT)
+}
+
+impl Parser for Aff {
+    fn run(&mut self, path: String) -> io::Result<()> {
+        parsers::read_folder(self, &path)?;
+        self.finish()
+    }
 
+    fn finish(&mut self) -> io::Result<()> {
         let json = serde_json::to_string_pretty(&self)?;
         let mut log = File::create("aff.json")?;
-        log.write(json.as_bytes())?;
+        log.write_all(json.as_bytes())?;
+        raw::write_report("aff", self.files.values().map(|x| &x.raw))?;
+        let index = Index::load_or_build("aff.json", &self.entries());
+        self.search = SearchIndex::new(index);
 
         Ok(())
     }
-}
 
-impl Parser for Aff {
-    fn data_view(&self, ui: &mut egui::Ui, filter: &str) {
+    fn read_file(&mut self, file_name: &str, f: &mut SnoReader, build: Option<u32>) -> io::Result<()> {
+        let records = game_build::records(build);
+        let (info_offset, info_len, hash_id) = Aff::header(f)?;
+        self.new_file(file_name, hash_id);
+
+        let num_values = info_len/records.aff_value;
+        for i in 0..num_values {
+            let start = i*records.aff_value;
+            utils::go_to(f, utils::record_pos(info_offset, i, records.aff_value)?)?;
+            f.begin("value");
+            let key_pos = start.checked_add(KEY_OFFSET)
+                .and_then(align)
+                .and_then(|x| x.checked_add(info_offset))
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("key of value {} is out of range", i)))?;
+            utils::go_to(f, key_pos.into())?;
+            let value = Aff::info(f)?;
+            self.add_field(file_name, value.replace(char::from(0), ""));
+        }
+        self.files.get_mut(file_name).unwrap().raw = f.take_raw();
+
+        Ok(())
+    }
+
+    fn data_view(&mut self, ui: &mut egui::Ui, query: &Query, reveal: Option<&Reveal>) {
+        if reveal.is_some() {
+            self.table.show = false;
+        }
+        ui.horizontal(|h| {
+            h.selectable_value(&mut self.table.show, false, "list");
+            h.selectable_value(&mut self.table.show, true, "table");
+        });
         let files = &self.files;
+        if self.table.show {
+            // the rows are only collected again when the search changed
+            if self.table.is_stale(query) {
+                self.table.set_rows(query, Aff::table_rows(files, &Aff::found_keys(files, &mut self.search, query)));
+            }
+            self.table.show(ui, "affix value");
+            return;
+        }
+        let keys = Aff::found_keys(files, &mut self.search, query);
         egui::Grid::new("aff_grid")
             .show(ui, |ui| {                        
-                for item in files.keys().filter(|x| filter.is_empty() || x.to_lowercase().contains(filter)).sorted() {
+                for item in keys {
                     if files[item].values.len() > 0 {
-                        ui.collapsing(item, |ui| {
+                        parsers::file_header(ui, item, reveal.is_some_and(|x| x.is_file(item)), |ui| {
                             ui.horizontal(|h| {
                                 h.strong("hash_id:");
                                 h.label(format!("{} ({:X})", files[item].hash_id, files[item].hash_id));
                             });
                             let values = &files[item];
                             for value in values.values.iter() {
-                                ui.horizontal(|ui| {
+                                parsers::field_row(ui, reveal.is_some_and(|x| x.is_field(item, "value", value)), |ui| {
                                     ui.label(value);
                                 });
                             }
@@ -119,10 +203,11 @@ impl Parser for Aff {
     }
 
     fn load_data_file(&self) -> Option<Box<dyn Parser>> {
-        match utils::load_or_pick_data_file("aff.json") {
-            Some(buf) => {
-                let data_str = String::from_utf8(buf).unwrap();
-                let aff: Aff = serde_json::from_str(&data_str).unwrap();
+        match utils::data_file_path("aff.json") {
+            Some(path) => {
+                let mut aff = Aff::load(&path).unwrap();
+                let index = Index::load_or_build(&path, &aff.entries());
+                aff.search = SearchIndex::new(index);
                 Some(Box::new(aff) as Box<dyn Parser>)
             },
             _ => None
@@ -132,6 +217,18 @@ impl Parser for Aff {
     fn tab_title(&self) -> String {
         ".aff".to_owned()
     }
+
+    fn hash_ids(&self) -> Vec<(String, u32)> {
+        self.files.iter().map(|(k, v)| (k.to_owned(), v.hash_id)).collect()
+    }
+
+    fn entries(&self) -> Vec<Entry<'_>> {
+        self.files.iter().map(|(k, v)| Aff::entry(k, v)).collect()
+    }
+
+    fn index(&self) -> Option<&Index> {
+        self.search.index()
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -139,14 +236,61 @@ pub struct AffFile {
     pub hash_id: u32,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     #[serde(default)]
-    pub values: Vec<String>
+    pub values: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub raw: Vec<RawRegion>
 }
 
 impl AffFile {
     fn new(hash_id: u32) -> Self {
         Self {
             hash_id,
-            values: Vec::new()
+            values: Vec::new(),
+            raw: Vec::new()
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// offsets stored in a file without payload are relative to the end of its first line
+    const OFFSET_BASE: u32 = 0x10;
+    const INFO_POS: u32 = 0x100;
+
+    fn put_u32(buf: &mut [u8], pos: u32, value: u32) {
+        buf[pos as usize..pos as usize + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// a file with a value record per key, `value_size` bytes apart
+    fn file(keys: &[&str], value_size: u32) -> Vec<u8> {
+        let table_len = keys.len() as u32*value_size;
+        let strings_pos = align(INFO_POS + table_len + 24).unwrap();
+        let mut buf = vec![0u8; strings_pos as usize];
+        put_u32(&mut buf, 0, 0xDEADBEEF);
+        put_u32(&mut buf, 0xC, 0x1234);
+        put_u32(&mut buf, 0x90, INFO_POS - OFFSET_BASE);
+        put_u32(&mut buf, 0x94, table_len);
+        for (i, key) in keys.iter().enumerate() {
+            let key_pos = INFO_POS + align(i as u32*value_size + KEY_OFFSET).unwrap();
+            let string_pos = buf.len() as u32;
+            put_u32(&mut buf, key_pos, string_pos - OFFSET_BASE);
+            put_u32(&mut buf, key_pos + 4, key.len() as u32 + 1);
+            buf.extend(key.as_bytes());
+            buf.push(0);
+        }
+        buf
+    }
+
+    #[test]
+    fn values_are_read_at_their_own_offsets() {
+        let keys = ["Alpha", "Beta", "Gamma", "Delta", "Epsilon"];
+        let mut aff = Aff::new();
+        aff.read_file("test.aff", &mut SnoReader::new(file(&keys, 44), None), None).unwrap();
+        let file = &aff.files["test.aff"];
+        assert_eq!(file.hash_id, 0x1234);
+        assert_eq!(file.values, keys);
+    }
+}
@@ -0,0 +1,412 @@
+use std::{io::{self, Write, Seek}, fs::File, collections::HashMap};
+use itertools::Itertools;
+use serde::{Serialize, Deserialize};
+use serde_json;
+
+use super::{self as parsers, Parser, Reveal, stl::{Stl, ListRef, NAME_KEY, DESCRIPTION_KEY}, aff::Aff};
+use crate::{utils, sno::SnoReader, query::{Query, Entry}, index::Index};
+
+/// affix type, power, class mask and slot mask
+const AFFIX_FIELDS: u64 = 0x20;
+/// offset and length of the value ranges
+const RANGES: u64 = 0x40;
+const CLASSES: [&str; 5] = ["Barbarian", "Druid", "Necromancer", "Rogue", "Sorcerer"];
+const SLOTS: [&str; 10] = ["Helm", "Chest", "Gloves", "Pants", "Boots", "Amulet", "Ring", "Weapon", "Offhand", "Shield"];
+
+/// legendary aspects, extracted from the `.aff` files that carry a legendary power
+#[derive(Serialize, Deserialize)]
+pub struct Aspects {
+    pub files: HashMap<String, Aspect>
+}
+
+impl Aspects {
+    pub fn new() -> Self {
+        Self {
+            files: HashMap::new()
+        }
+    }
+
+    /// returns the aspect without value ranges, plus offset and length of the range block
+    ///
+    /// the hash_id comes from the affix header, the aspect fields sit in the block it skips: the power
+    /// and masks on the second line at 0x20 and the range reference at 0x40
+    fn header(f: &mut SnoReader) -> io::Result<(Aspect, u32, u32)> {
+        let (_info_offset, _info_len, hash_id) = Aff::header(f)?;
+
+        utils::go_to(f, AFFIX_FIELDS)?;
+        let _affix_type = utils::read_u32(f)?;
+        let power = utils::read_u32(f)?;
+        let class_mask = utils::read_u32(f)?;
+        let slot_mask = utils::read_u32(f)?;
+
+        utils::go_to(f, RANGES)?;
+        let ranges_offset = utils::read_ptr(f)?;
+        let ranges_len = utils::read_u32(f)?;
+
+        let aspect = Aspect::new(hash_id, power, Aspects::mask_names(class_mask, &CLASSES), Aspects::mask_names(slot_mask, &SLOTS));
+
+        Ok((aspect, ranges_offset, ranges_len))
+    }
+
+    fn get_ranges(f: &mut SnoReader, offset: u32, len: u32) -> io::Result<Vec<ValueRange>> {
+        let mut ranges = Vec::new();
+        let old_pos = f.stream_position()?;
+        utils::go_to(f, offset.into())?;
+
+        let num_ranges = len/8;
+        for _ in 0..num_ranges {
+            let min = utils::read_f32(f)?;
+            let max = utils::read_f32(f)?;
+            ranges.push(ValueRange { min, max });
+        }
+
+        utils::go_to(f, old_pos)?;
+
+        Ok(ranges)
+    }
+
+    /// an empty mask means no restriction, so every name is returned
+    fn mask_names(mask: u32, names: &[&str]) -> Vec<String> {
+        names.iter()
+            .enumerate()
+            .filter(|(i, _)| mask == 0 || mask & (1 << i) != 0)
+            .map(|(_, name)| name.to_string())
+            .collect()
+    }
+
+    /// fills in names and rendered descriptions from a previously parsed string list dump
+    ///
+    /// the affix header has no string list id that is known, so the list is always the one named
+    /// after the affix and the reference is marked as guessed
+    pub fn resolve_strings(&mut self, stl: &Stl) {
+        let ids = stl.ids();
+        for (file_name, aspect) in self.files.iter_mut() {
+            if let Some(strings) = aspect.string_list.resolve(file_name, stl, &ids) {
+                aspect.name = strings.fields.get(NAME_KEY).cloned();
+                aspect.template = strings.fields.get(DESCRIPTION_KEY).cloned();
+                aspect.description = aspect.template.as_ref().map(|x| render(x, &aspect.ranges));
+            }
+        }
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("file,hash_id,name,power,classes,slots,ranges,description\n");
+        for file_name in self.files.keys().sorted() {
+            let aspect = &self.files[file_name];
+            let row = [
+                file_name.to_owned(),
+                aspect.hash_id.to_string(),
+                aspect.name.clone().unwrap_or_default(),
+                aspect.power.to_string(),
+                aspect.classes.join("|"),
+                aspect.slots.join("|"),
+                aspect.ranges.iter().map(|x| x.to_string()).join("|"),
+                aspect.description.clone().unwrap_or_default()
+            ];
+            csv.push_str(&row.iter().map(|x| csv_field(x)).join(","));
+            csv.push('\n');
+        }
+
+        csv
+    }
+
+    /// the aspect as a search entry, found by its own hash_id or its power's
+    fn entry<'a>(file_name: &'a str, aspect: &'a Aspect) -> Entry<'a> {
+        Entry::new(file_name, vec![aspect.hash_id, aspect.power])
+            .field_opt("name", aspect.name.as_ref())
+            .field_opt("description", aspect.description.as_ref())
+            .field("classes", aspect.classes.join(", "))
+            .field("slots", aspect.slots.join(", "))
+    }
+}
+
+impl Parser for Aspects {
+    fn run(&mut self, path: String) -> io::Result<()> {
+        parsers::read_folder(self, &path)?;
+        self.finish()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if let Ok(stl) = Stl::load("stl.json") {
+            self.resolve_strings(&stl);
+        }
+
+        let json = serde_json::to_string_pretty(&self)?;
+        let mut log = File::create("aspects.json")?;
+        log.write_all(json.as_bytes())?;
+
+        let mut csv = File::create("aspects.csv")?;
+        csv.write_all(self.to_csv().as_bytes())?;
+
+        Ok(())
+    }
+
+    fn read_file(&mut self, file_name: &str, f: &mut SnoReader, _build: Option<u32>) -> io::Result<()> {
+        let (mut aspect, ranges_offset, ranges_len) = Aspects::header(f)?;
+        if aspect.power == 0 { // not a legendary affix
+            return Ok(());
+        }
+        f.begin("ranges");
+        aspect.ranges = Aspects::get_ranges(f, ranges_offset, ranges_len)?;
+        self.files.insert(file_name.to_owned(), aspect);
+
+        Ok(())
+    }
+
+    fn data_view(&mut self, ui: &mut egui::Ui, query: &Query, reveal: Option<&Reveal>) {
+        let files = &self.files;
+        egui::Grid::new("aspect_grid")
+            .show(ui, |ui| {
+                for file_key in files.keys().filter(|x| query.matches(&Aspects::entry(x, &files[*x]))).sorted() {
+                    let aspect = &files[file_key];
+                    parsers::file_header(ui, aspect.name.as_deref().unwrap_or(file_key), reveal.is_some_and(|x| x.is_file(file_key)), |ui| {
+                        ui.horizontal(|h| {
+                            h.strong("file:");
+                            h.label(file_key);
+                        });
+                        ui.horizontal(|h| {
+                            h.strong("hash_id:");
+                            h.label(format!("{} ({:X})", aspect.hash_id, aspect.hash_id));
+                        });
+                        ui.horizontal(|h| {
+                            h.strong("power:");
+                            h.label(format!("{} ({:X})", aspect.power, aspect.power));
+                        });
+                        ui.horizontal(|h| {
+                            h.strong("classes:");
+                            h.label(aspect.classes.join(", "));
+                        });
+                        ui.horizontal(|h| {
+                            h.strong("slots:");
+                            h.label(aspect.slots.join(", "));
+                        });
+                        ui.horizontal(|h| {
+                            h.strong("ranges:");
+                            h.label(aspect.ranges.iter().map(|x| x.to_string()).join(", "));
+                        });
+                        ui.horizontal_wrapped(|h| {
+                            h.strong("description:");
+                            h.label(aspect.description.as_deref().unwrap_or("<missing>"));
+                        });
+                    });
+                    ui.end_row();
+                }
+            });
+    }
+
+    fn load_data_file(&self) -> Option<Box<dyn Parser>> {
+        match utils::load_or_pick_data_file("aspects.json") {
+            Some(buf) => {
+                let data_str = String::from_utf8(buf).unwrap();
+                let aspects: Aspects = serde_json::from_str(&data_str).unwrap();
+                Some(Box::new(aspects) as Box<dyn Parser>)
+            },
+            _ => None
+        }
+    }
+
+    fn tab_title(&self) -> String {
+        "aspects".to_owned()
+    }
+
+    fn hash_ids(&self) -> Vec<(String, u32)> {
+        self.files.iter().map(|(k, v)| (k.to_owned(), v.hash_id)).collect()
+    }
+
+    fn entries(&self) -> Vec<Entry<'_>> {
+        self.files.iter().map(|(k, v)| Aspects::entry(k, v)).collect()
+    }
+
+    fn index(&self) -> Option<&Index> {
+        None
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Aspect {
+    pub hash_id: u32,
+    pub power: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub string_list: ListRef,
+    pub classes: Vec<String>,
+    pub slots: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub ranges: Vec<ValueRange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub template: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub description: Option<String>
+}
+
+impl Aspect {
+    fn new(hash_id: u32, power: u32, classes: Vec<String>, slots: Vec<String>) -> Self {
+        Self {
+            hash_id,
+            power,
+            name: None,
+            string_list: ListRef::default(),
+            classes,
+            slots,
+            ranges: Vec::new(),
+            template: None,
+            description: None
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ValueRange {
+    pub min: f32,
+    pub max: f32
+}
+
+impl ValueRange {
+    fn scaled(&self, scale: f32) -> String {
+        let min = format_number(self.min * scale);
+        let max = format_number(self.max * scale);
+        match min == max {
+            true => min,
+            false => format!("{}-{}", min, max)
+        }
+    }
+}
+
+impl std::fmt::Display for ValueRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.scaled(1f32))
+    }
+}
+
+fn format_number(value: f32) -> String {
+    let rounded = (value * 100f32).round() / 100f32;
+    format!("{}", rounded)
+}
+
+fn csv_field(value: &str) -> String {
+    match value.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_owned()
+    }
+}
+
+/// renders a description template, replacing `[...]` value expressions with their ranges
+/// and dropping colour and icon markup such as `{c_number}` and `{/c}`
+pub fn render(template: &str, ranges: &[ValueRange]) -> String {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find(['[', '{']) {
+        out.push_str(&rest[..start]);
+        let close = match &rest[start..start + 1] {
+            "[" => ']',
+            _ => '}'
+        };
+        let end = match rest[start..].find(close) {
+            Some(end) => start + end,
+            None => {
+                rest = &rest[start..];
+                break;
+            }
+        };
+        let inner = &rest[start + 1..end];
+        if close == ']' {
+            out.push_str(&render_expression(inner, ranges));
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+
+    out
+}
+
+/// `[Affix_Value_1*100|%|]` renders as the first range, scaled, with a percent sign
+fn render_expression(expression: &str, ranges: &[ValueRange]) -> String {
+    let index = expression.find("Affix_Value_")
+        .map(|x| &expression[x + "Affix_Value_".len()..])
+        .or_else(|| expression.find("VALUE").map(|x| &expression[x + "VALUE".len()..]))
+        .and_then(|x| x.chars().take_while(|c| c.is_ascii_digit()).collect::<String>().parse::<usize>().ok())
+        .unwrap_or(1);
+    let range = match ranges.get(index.saturating_sub(1)) {
+        Some(range) => range,
+        None => return format!("[{}]", expression)
+    };
+    let scale = match expression.contains("*100") {
+        true => 100f32,
+        false => 1f32
+    };
+    let suffix = match expression.contains("|%") {
+        true => "%",
+        false => ""
+    };
+
+    format!("{}{}", range.scaled(scale), suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parsers::stl::StlFile;
+
+    use super::*;
+
+    fn ranges() -> Vec<ValueRange> {
+        vec![ValueRange { min: 0.1, max: 0.25 }, ValueRange { min: 3.0, max: 3.0 }]
+    }
+
+    #[test]
+    fn templates_render_their_value_ranges() {
+        assert_eq!(render("Deal [Affix_Value_1*100|%|] more", &ranges()), "Deal 10-25% more");
+        assert_eq!(render("Gain [Affix_Value_2] stacks", &ranges()), "Gain 3 stacks");
+        assert_eq!(render("Gain [{VALUE2}] and [Affix_Value_1|%|]", &ranges()), "Gain 3 and 0.1-0.25%");
+        assert_eq!(render("Unknown [Affix_Value_3]", &ranges()), "Unknown [Affix_Value_3]");
+        assert_eq!(render("{c_number}[Affix_Value_2]{/c} {icon:bullet}hit", &ranges()), "3 hit");
+    }
+
+    #[test]
+    fn unterminated_markup_is_kept() {
+        assert_eq!(render("deal [Affix_Value_1 damage", &ranges()), "deal [Affix_Value_1 damage");
+        assert_eq!(render("[Affix_Value_2] {c_red", &ranges()), "3 {c_red");
+    }
+
+    #[test]
+    fn empty_masks_allow_everything() {
+        assert_eq!(Aspects::mask_names(0, &CLASSES), CLASSES);
+        assert_eq!(Aspects::mask_names(0b101, &CLASSES), ["Barbarian", "Necromancer"]);
+        assert_eq!(Aspects::mask_names(1 << 9, &SLOTS), ["Shield"]);
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a, b"), "\"a, b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn strings_come_from_the_list_named_after_the_affix() {
+        let mut aspects = Aspects::new();
+        let mut aspect = Aspect::new(1, 2, Vec::new(), Vec::new());
+        aspect.ranges = ranges();
+        aspects.files.insert("Legendary_Fire.aff".to_owned(), aspect);
+        let mut stl = Stl::new();
+        stl.files.insert("Legendary_Fire.stl".to_owned(), StlFile {
+            hash_id: 7,
+            fields: HashMap::from([
+                (NAME_KEY.to_owned(), "Aspect of Fire".to_owned()),
+                (DESCRIPTION_KEY.to_owned(), "Burn for [Affix_Value_1*100|%|]".to_owned())
+            ]),
+            raw: Vec::new()
+        });
+
+        aspects.resolve_strings(&stl);
+        let aspect = &aspects.files["Legendary_Fire.aff"];
+        assert_eq!(aspect.name.as_deref(), Some("Aspect of Fire"));
+        assert_eq!(aspect.description.as_deref(), Some("Burn for 10-25%"));
+        assert_eq!((aspect.string_list.file.as_deref(), aspect.string_list.guessed), (Some("Legendary_Fire.stl"), true));
+    }
+}
@@ -0,0 +1,190 @@
+use std::{io::{self, Write}, fs::File, collections::HashMap, borrow::Cow};
+use egui::CollapsingHeader;
+use itertools::Itertools;
+use serde::{Serialize, Deserialize};
+use serde_json::{self, Value};
+
+use super::{self as parsers, Parser, Reveal};
+use crate::{utils, sno::SnoReader, layout::{self, Layout, Layouts}, query::{Query, Entry}, index::Index};
+
+/// files of any format described in a layouts file, stored as plain json values
+#[derive(Serialize, Deserialize)]
+pub struct Generic {
+    pub extension: String,
+    pub files: HashMap<String, Value>,
+    /// layouts of the extension, the one matching the parsed folder's game build is used
+    #[serde(skip)]
+    layouts: Vec<Layout>
+}
+
+impl Generic {
+    pub fn new(extension: &str, layouts: Vec<Layout>) -> Self {
+        Self {
+            extension: extension.to_owned(),
+            files: HashMap::new(),
+            layouts
+        }
+    }
+
+    /// a parser for the extension if `layouts.toml` describes it
+    pub fn from_layouts(extension: &str) -> io::Result<Option<Self>> {
+        let layouts = Layouts::load()?.for_extension(extension);
+        match layouts.is_empty() {
+            true => Ok(None),
+            false => Ok(Some(Generic::new(extension, layouts)))
+        }
+    }
+
+    /// `reveal` is the path of the revealed field when it is in this value, its headers are opened
+    /// down to the field, the other children keep their own state
+    fn value_view(ui: &mut egui::Ui, key: &str, path: &str, value: &Value, reveal: Option<&str>) {
+        let reveal = reveal.filter(|x| contains_path(path, x));
+        match value {
+            Value::Object(values) => {
+                CollapsingHeader::new(key).open(reveal.map(|_| true)).show(ui, |ui| {
+                    for (key, value) in values {
+                        let path = match path.is_empty() {
+                            true => key.to_owned(),
+                            false => format!("{}.{}", path, key)
+                        };
+                        Generic::value_view(ui, key, &path, value, reveal);
+                    }
+                });
+            },
+            Value::Array(values) => {
+                CollapsingHeader::new(format!("{} [{}]", key, values.len())).open(reveal.map(|_| true)).show(ui, |ui| {
+                    for (i, value) in values.iter().enumerate() {
+                        Generic::value_view(ui, &i.to_string(), &format!("{}[{}]", path, i), value, reveal);
+                    }
+                });
+            },
+            Value::Number(number) if number.is_u64() => {
+                parsers::field_row(ui, reveal == Some(path), |h| {
+                    h.strong(format!("{}:", key));
+                    h.label(format!("{} ({:X})", number, number.as_u64().unwrap()));
+                });
+            },
+            Value::String(text) => {
+                parsers::field_row(ui, reveal == Some(path), |h| {
+                    h.strong(format!("{}:", key));
+                    h.label(text);
+                });
+            },
+            _ => {
+                parsers::field_row(ui, reveal == Some(path), |h| {
+                    h.strong(format!("{}:", key));
+                    h.label(value.to_string());
+                });
+            }
+        }
+    }
+
+    /// the file as a search entry, with a field per value named by its path
+    fn entry<'a>(file_name: &'a str, value: &'a Value) -> Entry<'a> {
+        let hashes = value.get("hash_id").and_then(|x| x.as_u64()).map(|x| vec![x as u32]).unwrap_or_default();
+        let mut entry = Entry::new(file_name, hashes);
+        Generic::flatten(String::new(), value, &mut entry);
+
+        entry
+    }
+
+    fn flatten<'a>(path: String, value: &'a Value, entry: &mut Entry<'a>) {
+        match value {
+            Value::Object(values) => {
+                for (key, value) in values {
+                    let path = match path.is_empty() {
+                        true => key.to_owned(),
+                        false => format!("{}.{}", path, key)
+                    };
+                    Generic::flatten(path, value, entry);
+                }
+            },
+            Value::Array(values) => {
+                for (i, value) in values.iter().enumerate() {
+                    Generic::flatten(format!("{}[{}]", path, i), value, entry);
+                }
+            },
+            Value::String(text) => entry.fields.push((Cow::Owned(path), Cow::Borrowed(text.as_str()))),
+            value => entry.fields.push((Cow::Owned(path), Cow::Owned(value.to_string())))
+        }
+    }
+}
+
+/// whether the field at `key` is the value at `path` or one of its children, every field is in the
+/// file's root value
+fn contains_path(path: &str, key: &str) -> bool {
+    path.is_empty() || key.strip_prefix(path).is_some_and(|x| x.is_empty() || x.starts_with('.') || x.starts_with('['))
+}
+
+impl Parser for Generic {
+    fn run(&mut self, path: String) -> io::Result<()> {
+        parsers::read_folder(self, &path)?;
+        self.finish()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(&self)?;
+        let mut log = File::create(format!("{}.json", self.extension))?;
+        log.write_all(json.as_bytes())?;
+
+        Ok(())
+    }
+
+    fn read_file(&mut self, file_name: &str, f: &mut SnoReader, build: Option<u32>) -> io::Result<()> {
+        let layout = match layout::for_build(&self.layouts, build) {
+            Some(layout) => layout,
+            None => return Err(io::Error::new(io::ErrorKind::NotFound, format!("no layout for .{} in build {:?}", self.extension, build)))
+        };
+        let value = layout.read(f)?;
+        self.files.insert(file_name.to_owned(), value);
+
+        Ok(())
+    }
+
+    fn data_view(&mut self, ui: &mut egui::Ui, query: &Query, reveal: Option<&Reveal>) {
+        let files = &self.files;
+        egui::Grid::new("generic_grid")
+            .show(ui, |ui| {
+                for file_key in files.keys().filter(|x| query.matches(&Generic::entry(x, &files[*x]))).sorted() {
+                    let revealed = reveal.filter(|x| x.is_file(file_key));
+                    // scrolled to before the values, so a revealed field takes over the scroll
+                    if revealed.is_some() {
+                        ui.scroll_to_cursor(Some(egui::Align::TOP));
+                    }
+                    ui.push_id(file_key, |ui| {
+                        Generic::value_view(ui, file_key, "", &files[file_key], revealed.map(|x| x.key.as_str()));
+                    });
+                    ui.end_row();
+                }
+            });
+    }
+
+    fn load_data_file(&self) -> Option<Box<dyn Parser>> {
+        match utils::load_or_pick_data_file(&format!("{}.json", self.extension)) {
+            Some(buf) => {
+                let data_str = String::from_utf8(buf).unwrap();
+                let generic: Generic = serde_json::from_str(&data_str).unwrap();
+                Some(Box::new(generic) as Box<dyn Parser>)
+            },
+            _ => None
+        }
+    }
+
+    fn tab_title(&self) -> String {
+        format!(".{}", self.extension)
+    }
+
+    fn hash_ids(&self) -> Vec<(String, u32)> {
+        self.files.iter()
+            .filter_map(|(k, v)| v.get("hash_id").and_then(|x| x.as_u64()).map(|x| (k.to_owned(), x as u32)))
+            .collect()
+    }
+
+    fn entries(&self) -> Vec<Entry<'_>> {
+        self.files.iter().map(|(k, v)| Generic::entry(k, v)).collect()
+    }
+
+    fn index(&self) -> Option<&Index> {
+        None
+    }
+}
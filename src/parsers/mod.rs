@@ -1,9 +1,143 @@
+use std::{io, fs, path::{Path, PathBuf}};
+
+use crate::{game_build, layout::Layouts, sno::SnoReader, query::{Query, Entry}, index::Index};
+
 pub mod stl;
 pub mod aff;
 pub mod skl;
+pub mod qst;
+pub mod mon;
+pub mod ach;
+pub mod aspect;
+pub mod generic;
+
+/// every file extension with a parser, in the order they are listed in the ui
+pub const EXTENSIONS: [&str; 6] = ["stl", "aff", "skl", "qst", "mon", "ach"];
 
-pub trait Parser {
-    fn data_view(&self, ui: &mut egui::Ui, filter: &str);
+pub trait Parser: Send {
+    fn run(&mut self, path: String) -> io::Result<()>;
+    /// resolves names and writes the json and reports once every file is read
+    fn finish(&mut self) -> io::Result<()>;
+    /// reads one file into the parsed data, labelling records with `SnoReader::begin`
+    fn read_file(&mut self, file_name: &str, f: &mut SnoReader, build: Option<u32>) -> io::Result<()>;
+    /// shows the records the search finds, opening and scrolling to the `reveal` record
+    fn data_view(&mut self, ui: &mut egui::Ui, query: &Query, reveal: Option<&Reveal>);
     fn tab_title(&self) -> String;
     fn load_data_file(&self) -> Option<Box<dyn Parser>>;
-}
\ No newline at end of file
+    /// every parsed file with the sno id read from its header
+    fn hash_ids(&self) -> Vec<(String, u32)>;
+    /// every record as a search entry
+    fn entries(&self) -> Vec<Entry<'_>>;
+    /// the full text index built when the data was loaded, for the datasets that have one
+    fn index(&self) -> Option<&Index>;
+}
+
+/// the parser for an extension, `layouts.toml` is only read for extensions without a built in one
+pub fn from_extension(extension: &str) -> io::Result<Option<Box<dyn Parser>>> {
+    let parser: Box<dyn Parser> = match extension {
+        "stl" => Box::new(stl::Stl::new()),
+        "aff" => Box::new(aff::Aff::new()),
+        "skl" => Box::new(skl::Skl::new()),
+        "qst" => Box::new(qst::Qst::new()),
+        "mon" => Box::new(mon::Mon::new()),
+        "ach" => Box::new(ach::Ach::new()),
+        _ => return Ok(generic::Generic::from_layouts(extension)?.map(|x| Box::new(x) as Box<dyn Parser>))
+    };
+
+    Ok(Some(parser))
+}
+
+/// the built in extensions followed by those only described in `layouts.toml`
+pub fn all_extensions() -> io::Result<Vec<String>> {
+    let mut extensions: Vec<String> = EXTENSIONS.iter().map(|x| x.to_string()).collect();
+    for extension in Layouts::load()?.extensions() {
+        if !extensions.contains(&extension) {
+            extensions.push(extension);
+        }
+    }
+
+    Ok(extensions)
+}
+
+/// the records the search finds, answered by the dataset's index when it has one
+pub fn search<'a>(parser: &'a dyn Parser, query: &Query) -> Vec<Entry<'a>> {
+    let files = parser.index().and_then(|index| query.index_text().map(|x| index.files(x)));
+    parser.entries()
+        .into_iter()
+        .filter(|x| match &files {
+            Some(files) => files.contains(x.file),
+            None => query.matches(x)
+        })
+        .collect()
+}
+
+/// a search result to show in its tab
+pub struct Reveal {
+    pub file: String,
+    /// the field the search was found in, empty when the file name or a hash matched
+    pub key: String,
+    pub value: String,
+    /// every field of the record, telling apart the records of one file
+    pub fields: Vec<(String, String)>
+}
+
+impl Reveal {
+    pub fn is_file(&self, file: &str) -> bool {
+        self.file == file
+    }
+
+    /// whether the result was found in this field of the file
+    pub fn is_field(&self, file: &str, key: &str, value: &str) -> bool {
+        self.is_file(file) && self.key == key && self.value == value
+    }
+
+    /// whether the result is this record
+    pub fn is_entry(&self, entry: &Entry) -> bool {
+        self.is_file(entry.file) && self.fields.len() == entry.fields.len()
+            && self.fields.iter().zip(entry.fields.iter()).all(|(a, b)| a.0 == b.0 && a.1 == b.1)
+    }
+}
+
+/// a collapsing header for one file, opened and scrolled to when a search result points at it
+pub fn file_header(ui: &mut egui::Ui, title: impl Into<egui::WidgetText>, reveal: bool, add_contents: impl FnOnce(&mut egui::Ui)) {
+    // scrolled to before the contents, so a revealed field inside takes over the scroll
+    if reveal {
+        ui.scroll_to_cursor(Some(egui::Align::TOP));
+    }
+    egui::CollapsingHeader::new(title)
+        .open(reveal.then_some(true))
+        .show(ui, add_contents);
+}
+
+/// one field of a file, scrolled to when a search result was found in it
+pub fn field_row(ui: &mut egui::Ui, reveal: bool, add_contents: impl FnOnce(&mut egui::Ui)) {
+    let response = ui.horizontal(add_contents).response;
+    if reveal {
+        response.scroll_to_me(Some(egui::Align::Center));
+    }
+}
+
+/// reads every file of a folder with the folder's game build
+pub fn read_folder(parser: &mut dyn Parser, path: &str) -> io::Result<()> {
+    let build = game_build::detect(Path::new(path));
+    for file in fs::read_dir(path)? {
+        let f_u = file?;
+        let file_name = f_u.file_name().to_str().unwrap().to_owned();
+
+        let mut f = SnoReader::open(&f_u.path())?;
+        parser.read_file(&file_name, &mut f, build)?;
+    }
+
+    Ok(())
+}
+
+/// runs the matching parser on every (extension, folder) pair, skipping extensions without one
+pub fn run_folders(folders: &[(String, PathBuf)]) -> io::Result<()> {
+    for (extension, folder) in folders {
+        if let Some(mut parser) = from_extension(extension)? {
+            parser.run(folder.display().to_string())?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,316 @@
+use std::{io::{self, Write, Seek}, fs::File, collections::HashMap};
+use itertools::Itertools;
+use serde::{Serialize, Deserialize};
+use serde_json;
+
+use super::{self as parsers, Parser, Reveal, stl::{Stl, ListRef, NAME_KEY}};
+use crate::{utils, game_build, raw::{self, RawRegion}, sno::SnoReader, query::{Query, Entry}, index::Index};
+
+const RESISTANCES: [&str; 6] = ["physical", "fire", "cold", "lightning", "poison", "shadow"];
+
+#[derive(Serialize, Deserialize)]
+pub struct Mon {
+    pub files: HashMap<String, MonFile>
+}
+
+impl Mon {
+    pub fn new() -> Self {
+        Self {
+            files: HashMap::new()
+        }
+    }
+
+    fn header(f: &mut SnoReader) -> io::Result<(MonFile, u32, u32, u32, u32)> {
+        let _deadbeef = utils::read_u32(f)?;
+        let _file_type = utils::read_u32(f)?;
+        utils::padding(f, 4)?;
+        let hash_id = utils::read_u32(f)?;
+        utils::padding(f, 16)?; // end first line
+
+        let family = utils::read_u32(f)?;
+        let monster_type = utils::read_u32(f)?;
+        let level_min = utils::read_u32(f)?;
+        let level_max = utils::read_u32(f)?;
+        let mut resistances = [0f32; 6];
+        for resistance in resistances.iter_mut() {
+            *resistance = utils::read_f32(f)?;
+        }
+        let string_list_id = utils::read_u32(f)?;
+        utils::padding(f, 4)?; // end second line
+
+        let attributes_offset = utils::read_ptr(f)?;
+        let attributes_len = utils::read_u32(f)?;
+        utils::padding(f, 8)?;
+
+        let powers_offset = utils::read_ptr(f)?;
+        let powers_len = utils::read_u32(f)?;
+        utils::padding(f, 8)?;
+
+        let mut mon_file = MonFile::new(hash_id, family, monster_type, level_min, level_max, resistances);
+        mon_file.string_list = ListRef::new(string_list_id);
+
+        Ok((mon_file, attributes_offset, attributes_len, powers_offset, powers_len))
+    }
+
+    fn attribute(f: &mut SnoReader) -> io::Result<MonAttribute> {
+        let id = utils::read_u32(f)?;
+        let param = utils::read_u32(f)?;
+        let value = utils::read_f32(f)?;
+        utils::padding(f, 4)?;
+
+        Ok(MonAttribute { id, param, value })
+    }
+
+    fn get_powers(f: &mut SnoReader, offset: u32, len: u32) -> io::Result<Vec<u32>> {
+        let mut powers = Vec::new();
+        let old_pos = f.stream_position()?;
+        utils::go_to(f, offset.into())?;
+
+        let num_powers = len/4;
+        for _ in 0..num_powers {
+            powers.push(utils::read_u32(f)?);
+        }
+
+        utils::go_to(f, old_pos)?;
+
+        Ok(powers)
+    }
+
+    /// fills in monster names from a previously parsed string list dump
+    pub fn resolve_strings(&mut self, stl: &Stl) {
+        let ids = stl.ids();
+        for (file_name, file) in self.files.iter_mut() {
+            if let Some(strings) = file.string_list.resolve(file_name, stl, &ids) {
+                file.name = strings.fields.get(NAME_KEY).cloned();
+            }
+        }
+    }
+
+    /// the file as a search entry, found by its own hash_id or its powers
+    fn entry<'a>(file_name: &'a str, file: &'a MonFile) -> Entry<'a> {
+        let hashes = [file.hash_id].into_iter().chain(file.powers.iter().copied()).collect();
+        Entry::new(file_name, hashes)
+            .field_opt("name", file.name.as_ref())
+            .field("family", file.family.to_string())
+            .field("monster_type", file.monster_type.to_string())
+    }
+}
+
+impl Parser for Mon {
+    fn run(&mut self, path: String) -> io::Result<()> {
+        parsers::read_folder(self, &path)?;
+        self.finish()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if let Ok(stl) = Stl::load("stl.json") {
+            self.resolve_strings(&stl);
+        }
+
+        let json = serde_json::to_string_pretty(&self)?;
+        let mut log = File::create("mon.json")?;
+        log.write_all(json.as_bytes())?;
+        raw::write_report("mon", self.files.values().map(|x| &x.raw))?;
+
+        Ok(())
+    }
+
+    fn read_file(&mut self, file_name: &str, f: &mut SnoReader, build: Option<u32>) -> io::Result<()> {
+        let records = game_build::records(build);
+        let (mut mon_file, attributes_offset, attributes_len, powers_offset, powers_len) = Mon::header(f)?;
+        mon_file.powers = Mon::get_powers(f, powers_offset, powers_len)?;
+
+        let num_attributes = attributes_len/records.mon_attribute;
+        for i in 0..num_attributes {
+            utils::go_to(f, utils::record_pos(attributes_offset, i, records.mon_attribute)?)?;
+            f.begin("attribute");
+            mon_file.attributes.push(Mon::attribute(f)?);
+        }
+
+        mon_file.raw = f.take_raw();
+        self.files.insert(file_name.to_owned(), mon_file);
+
+        Ok(())
+    }
+
+    fn data_view(&mut self, ui: &mut egui::Ui, query: &Query, reveal: Option<&Reveal>) {
+        let files = &self.files;
+        egui::Grid::new("mon_grid")
+            .show(ui, |ui| {
+                for file_key in files.keys().filter(|x| query.matches(&Mon::entry(x, &files[*x]))).sorted() {
+                    let monster = &files[file_key];
+                    parsers::file_header(ui, file_key, reveal.is_some_and(|x| x.is_file(file_key)), |ui| {
+                        ui.horizontal(|h| {
+                            h.strong("hash_id:");
+                            h.label(format!("{} ({:X})", monster.hash_id, monster.hash_id));
+                        });
+                        ui.horizontal(|h| {
+                            h.strong("name:");
+                            h.label(monster.name.as_deref().unwrap_or("<missing>"));
+                        });
+                        ui.horizontal(|h| {
+                            h.strong("family:");
+                            h.label(format!("{}", monster.family));
+                            h.strong("type:");
+                            h.label(format!("{}", monster.monster_type));
+                        });
+                        ui.horizontal(|h| {
+                            h.strong("level:");
+                            h.label(format!("{} - {}", monster.level_min, monster.level_max));
+                        });
+                        ui.collapsing("resistances", |ui| {
+                            for (name, value) in RESISTANCES.iter().zip(monster.resistances.iter()) {
+                                ui.horizontal(|h| {
+                                    h.strong(format!("{}:", name));
+                                    h.label(format!("{}", value));
+                                });
+                            }
+                        });
+                        ui.collapsing("attributes", |ui| {
+                            for attribute in monster.attributes.iter() {
+                                ui.label(format!("{} ({}): {}", attribute.id, attribute.param, attribute.value));
+                            }
+                        });
+                        ui.collapsing("powers", |ui| {
+                            for power in monster.powers.iter() {
+                                ui.label(format!("{} ({:X})", power, power));
+                            }
+                        });
+                    });
+                    ui.end_row();
+                }
+            });
+    }
+
+    fn load_data_file(&self) -> Option<Box<dyn Parser>> {
+        match utils::load_or_pick_data_file("mon.json") {
+            Some(buf) => {
+                let data_str = String::from_utf8(buf).unwrap();
+                let mon: Mon = serde_json::from_str(&data_str).unwrap();
+                Some(Box::new(mon) as Box<dyn Parser>)
+            },
+            _ => None
+        }
+    }
+
+    fn tab_title(&self) -> String {
+        ".mon".to_owned()
+    }
+
+    fn hash_ids(&self) -> Vec<(String, u32)> {
+        self.files.iter().map(|(k, v)| (k.to_owned(), v.hash_id)).collect()
+    }
+
+    fn entries(&self) -> Vec<Entry<'_>> {
+        self.files.iter().map(|(k, v)| Mon::entry(k, v)).collect()
+    }
+
+    fn index(&self) -> Option<&Index> {
+        None
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MonFile {
+    pub hash_id: u32,
+    pub family: u32,
+    pub monster_type: u32,
+    pub level_min: u32,
+    pub level_max: u32,
+    pub resistances: [f32; 6],
+    #[serde(flatten)]
+    pub string_list: ListRef,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub attributes: Vec<MonAttribute>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub powers: Vec<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub raw: Vec<RawRegion>
+}
+
+impl MonFile {
+    fn new(hash_id: u32, family: u32, monster_type: u32, level_min: u32, level_max: u32, resistances: [f32; 6]) -> Self {
+        Self {
+            hash_id,
+            family,
+            monster_type,
+            level_min,
+            level_max,
+            resistances,
+            string_list: ListRef::default(),
+            name: None,
+            attributes: Vec::new(),
+            powers: Vec::new(),
+            raw: Vec::new()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MonAttribute {
+    pub id: u32,
+    pub param: u32,
+    pub value: f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// offsets stored in a file without payload are relative to the end of its first line
+    const OFFSET_BASE: u32 = 0x10;
+    const ATTRIBUTES_POS: u32 = 0x70;
+
+    fn put_u32(buf: &mut [u8], pos: u32, value: u32) {
+        buf[pos as usize..pos as usize + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// a monster with one attribute per value and two powers after them
+    fn file(values: &[f32]) -> Vec<u8> {
+        let attribute_size = game_build::records(None).mon_attribute;
+        let powers_pos = ATTRIBUTES_POS + values.len() as u32*attribute_size;
+        let mut buf = vec![0u8; (powers_pos + 8) as usize];
+        put_u32(&mut buf, 0, 0xDEADBEEF);
+        put_u32(&mut buf, 0xC, 0x1234);
+        put_u32(&mut buf, 0x20, 4);
+        put_u32(&mut buf, 0x24, 5);
+        put_u32(&mut buf, 0x28, 10);
+        put_u32(&mut buf, 0x2C, 20);
+        put_u32(&mut buf, 0x30, 0.5f32.to_bits());
+        put_u32(&mut buf, 0x44, (-0.25f32).to_bits());
+        put_u32(&mut buf, 0x48, 0x5678);
+        put_u32(&mut buf, 0x50, ATTRIBUTES_POS - OFFSET_BASE);
+        put_u32(&mut buf, 0x54, values.len() as u32*attribute_size);
+        put_u32(&mut buf, 0x60, powers_pos - OFFSET_BASE);
+        put_u32(&mut buf, 0x64, 8);
+        for (i, value) in values.iter().enumerate() {
+            let attribute_pos = ATTRIBUTES_POS + i as u32*attribute_size;
+            put_u32(&mut buf, attribute_pos, i as u32 + 1);
+            put_u32(&mut buf, attribute_pos + 4, 7);
+            put_u32(&mut buf, attribute_pos + 8, value.to_bits());
+        }
+        put_u32(&mut buf, powers_pos, 0xAAAA);
+        put_u32(&mut buf, powers_pos + 4, 0xBBBB);
+        buf
+    }
+
+    #[test]
+    fn header_and_attributes_are_read() {
+        let mut mon = Mon::new();
+        mon.read_file("test.mon", &mut SnoReader::new(file(&[1.5, 2.0, 3.25]), None), None).unwrap();
+        let file = &mon.files["test.mon"];
+        assert_eq!((file.hash_id, file.family, file.monster_type), (0x1234, 4, 5));
+        assert_eq!((file.level_min, file.level_max), (10, 20));
+        assert_eq!(file.resistances, [0.5, 0.0, 0.0, 0.0, 0.0, -0.25]);
+        assert_eq!(file.string_list.id, 0x5678);
+        assert_eq!(file.powers, [0xAAAA, 0xBBBB]);
+        let attributes = file.attributes.iter().map(|x| (x.id, x.param, x.value)).collect::<Vec<_>>();
+        assert_eq!(attributes, [(1, 7, 1.5), (2, 7, 2.0), (3, 7, 3.25)]);
+    }
+}
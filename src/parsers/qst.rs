@@ -0,0 +1,353 @@
+use std::{io::{self, Write, Seek}, fs::File, collections::HashMap};
+use egui::CollapsingHeader;
+use itertools::Itertools;
+use serde::{Serialize, Deserialize};
+use serde_json;
+
+use super::{self as parsers, Parser, Reveal, stl::{Stl, ListRef, NAME_KEY, DESCRIPTION_KEY}};
+use crate::{utils, game_build, raw::{self, RawRegion}, sno::SnoReader, query::{Query, Entry}, index::Index};
+
+#[derive(Serialize, Deserialize)]
+pub struct Qst {
+    pub files: HashMap<String, QstFile>
+}
+
+impl Qst {
+    pub fn new() -> Self {
+        Self {
+            files: HashMap::new()
+        }
+    }
+
+    fn new_file(&mut self, file_name: &str, hash_id: u32, quest_type: u32, string_list_id: u32) {
+        self.files.insert(file_name.to_owned(), QstFile::new(hash_id, quest_type, string_list_id));
+    }
+
+    fn add_step(&mut self, file_name: &str, step: QstStep) {
+        self.files.entry(file_name.to_string())
+            .and_modify(|k| {
+                k.steps.push(step);
+            });
+    }
+
+    /// returns hash_id, quest type, string list id, offset and length of the step block
+    fn header(f: &mut SnoReader) -> io::Result<(u32, u32, u32, u32, u32)> {
+        let _deadbeef = utils::read_u32(f)?;
+        let _file_type = utils::read_u32(f)?;
+        utils::padding(f, 4)?;
+        let hash_id = utils::read_u32(f)?;
+        utils::padding(f, 16)?; // end first line
+
+        let quest_type = utils::read_u32(f)?;
+        let string_list_id = utils::read_u32(f)?;
+        utils::padding(f, 24)?; // end second line
+
+        let steps_offset = utils::read_ptr(f)?;
+        let steps_len = utils::read_u32(f)?;
+        utils::padding(f, 8)?;
+
+        Ok((hash_id, quest_type, string_list_id, steps_offset, steps_len))
+    }
+
+    fn step(f: &mut SnoReader) -> io::Result<QstStep> {
+        let id = utils::read_u32(f)?;
+        let step_type = utils::read_u32(f)?;
+        utils::padding(f, 8)?;
+        let links_offset = utils::read_ptr(f)?;
+        let links_len = utils::read_u32(f)?;
+        let links = Qst::get_ids(f, links_offset, links_len)?;
+        utils::padding(f, 8)?;
+        let objectives_offset = utils::read_ptr(f)?;
+        let objectives_len = utils::read_u32(f)?;
+        let objectives = Qst::get_ids(f, objectives_offset, objectives_len)?;
+
+        Ok(QstStep::new(id, step_type, links, objectives))
+    }
+
+    fn get_ids(f: &mut SnoReader, offset: u32, len: u32) -> io::Result<Vec<u32>> {
+        let mut ids = Vec::new();
+        let old_pos = f.stream_position()?;
+        utils::go_to(f, offset.into())?;
+
+        let num_ids = len/4;
+        for _ in 0..num_ids {
+            ids.push(utils::read_u32(f)?);
+        }
+
+        utils::go_to(f, old_pos)?;
+
+        Ok(ids)
+    }
+
+    /// fills in quest names and descriptions from a previously parsed string list dump
+    pub fn resolve_strings(&mut self, stl: &Stl) {
+        let ids = stl.ids();
+        for (file_name, file) in self.files.iter_mut() {
+            if let Some(strings) = file.string_list.resolve(file_name, stl, &ids) {
+                file.name = strings.fields.get(NAME_KEY).cloned();
+                file.description = strings.fields.get(DESCRIPTION_KEY).cloned();
+            }
+        }
+    }
+
+    /// renders every quest's step graph as one graphviz digraph, one cluster per quest
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph quests {\n    node [shape=box];\n");
+        for (i, file_name) in self.files.keys().sorted().enumerate() {
+            let file = &self.files[file_name];
+            let label = file.name.as_deref().unwrap_or(file_name);
+            dot.push_str(&format!("    subgraph cluster_{} {{\n", i));
+            dot.push_str(&format!("        label=\"{}\";\n", label.replace('\\', "\\\\").replace('"', "\\\"")));
+            for step in file.steps.iter() {
+                dot.push_str(&format!("        \"{}:{}\" [label=\"{} (type {})\"];\n", file.hash_id, step.id, step.id, step.step_type));
+                for link in step.links.iter() {
+                    dot.push_str(&format!("        \"{}:{}\" -> \"{}:{}\";\n", file.hash_id, step.id, file.hash_id, link));
+                }
+            }
+            dot.push_str("    }\n");
+        }
+        dot.push_str("}\n");
+
+        dot
+    }
+
+    /// the file as a search entry, with its name and description
+    fn entry<'a>(file_name: &'a str, file: &'a QstFile) -> Entry<'a> {
+        Entry::new(file_name, vec![file.hash_id])
+            .field_opt("name", file.name.as_ref())
+            .field_opt("description", file.description.as_ref())
+            .field("quest_type", file.quest_type.to_string())
+    }
+}
+
+impl Parser for Qst {
+    fn run(&mut self, path: String) -> io::Result<()> {
+        parsers::read_folder(self, &path)?;
+        self.finish()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if let Ok(stl) = Stl::load("stl.json") {
+            self.resolve_strings(&stl);
+        }
+
+        let json = serde_json::to_string_pretty(&self)?;
+        let mut log = File::create("qst.json")?;
+        log.write_all(json.as_bytes())?;
+        raw::write_report("qst", self.files.values().map(|x| &x.raw))?;
+
+        let mut dot = File::create("qst.dot")?;
+        dot.write_all(self.to_dot().as_bytes())?;
+
+        Ok(())
+    }
+
+    fn read_file(&mut self, file_name: &str, f: &mut SnoReader, build: Option<u32>) -> io::Result<()> {
+        let records = game_build::records(build);
+        let (hash_id, quest_type, string_list_id, steps_offset, steps_len) = Qst::header(f)?;
+        self.new_file(file_name, hash_id, quest_type, string_list_id);
+
+        let num_steps = steps_len/records.qst_step;
+        for i in 0..num_steps {
+            utils::go_to(f, utils::record_pos(steps_offset, i, records.qst_step)?)?;
+            f.begin("step");
+            let step = Qst::step(f)?;
+            self.add_step(file_name, step);
+        }
+        self.files.get_mut(file_name).unwrap().raw = f.take_raw();
+
+        Ok(())
+    }
+
+    fn data_view(&mut self, ui: &mut egui::Ui, query: &Query, reveal: Option<&Reveal>) {
+        let files = &self.files;
+        egui::Grid::new("qst_grid")
+            .show(ui, |ui| {
+                for file_key in files.keys().filter(|x| query.matches(&Qst::entry(x, &files[*x]))).sorted() {
+                    let quest = &files[file_key];
+                    parsers::file_header(ui, file_key, reveal.is_some_and(|x| x.is_file(file_key)), |ui| {
+                        ui.horizontal(|h| {
+                            h.strong("hash_id:");
+                            h.label(format!("{} ({:X})", quest.hash_id, quest.hash_id));
+                        });
+                        ui.horizontal(|h| {
+                            h.strong("quest_type:");
+                            h.label(format!("{}", quest.quest_type));
+                        });
+                        ui.horizontal(|h| {
+                            h.strong("name:");
+                            h.label(quest.name.as_deref().unwrap_or("<missing>"));
+                        });
+                        ui.horizontal(|h| {
+                            h.strong("description:");
+                            h.label(quest.description.as_deref().unwrap_or("<missing>"));
+                        });
+                        for step in quest.steps.iter() {
+                            let step_header = CollapsingHeader::new(format!("step: {}", step.id))
+                                .id_source((quest.hash_id, step.id));
+                            step_header.show(ui, |ui| {
+                                ui.horizontal(|h| {
+                                    h.strong("step_type:");
+                                    h.label(format!("{}", step.step_type));
+                                });
+                                ui.horizontal(|h| {
+                                    h.strong("links:");
+                                    h.label(step.links.iter().join(", "));
+                                });
+                                ui.horizontal(|h| {
+                                    h.strong("objectives:");
+                                    h.label(step.objectives.iter().map(|x| format!("{:X}", x)).join(", "));
+                                });
+                            });
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+    }
+
+    fn load_data_file(&self) -> Option<Box<dyn Parser>> {
+        match utils::load_or_pick_data_file("qst.json") {
+            Some(buf) => {
+                let data_str = String::from_utf8(buf).unwrap();
+                let qst: Qst = serde_json::from_str(&data_str).unwrap();
+                Some(Box::new(qst) as Box<dyn Parser>)
+            },
+            _ => None
+        }
+    }
+
+    fn tab_title(&self) -> String {
+        ".qst".to_owned()
+    }
+
+    fn hash_ids(&self) -> Vec<(String, u32)> {
+        self.files.iter().map(|(k, v)| (k.to_owned(), v.hash_id)).collect()
+    }
+
+    fn entries(&self) -> Vec<Entry<'_>> {
+        self.files.iter().map(|(k, v)| Qst::entry(k, v)).collect()
+    }
+
+    fn index(&self) -> Option<&Index> {
+        None
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct QstFile {
+    pub hash_id: u32,
+    pub quest_type: u32,
+    #[serde(flatten)]
+    pub string_list: ListRef,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub steps: Vec<QstStep>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub raw: Vec<RawRegion>
+}
+
+impl QstFile {
+    fn new(hash_id: u32, quest_type: u32, string_list_id: u32) -> Self {
+        Self {
+            hash_id,
+            quest_type,
+            string_list: ListRef::new(string_list_id),
+            name: None,
+            description: None,
+            steps: Vec::new(),
+            raw: Vec::new()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct QstStep {
+    pub id: u32,
+    pub step_type: u32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub links: Vec<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub objectives: Vec<u32>
+}
+
+impl QstStep {
+    fn new(id: u32, step_type: u32, links: Vec<u32>, objectives: Vec<u32>) -> Self {
+        Self {
+            id,
+            step_type,
+            links,
+            objectives
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// offsets stored in a file without payload are relative to the end of its first line
+    const OFFSET_BASE: u32 = 0x10;
+    const STEPS_POS: u32 = 0x60;
+
+    fn put_u32(buf: &mut [u8], pos: u32, value: u32) {
+        buf[pos as usize..pos as usize + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// a quest whose steps each link to the next one and have their id as objective
+    fn file(step_ids: &[u32]) -> Vec<u8> {
+        let step_size = game_build::records(None).qst_step;
+        let ids_pos = STEPS_POS + step_ids.len() as u32*step_size;
+        let mut buf = vec![0u8; (ids_pos + step_ids.len() as u32*8) as usize];
+        put_u32(&mut buf, 0, 0xDEADBEEF);
+        put_u32(&mut buf, 0xC, 0x1234);
+        put_u32(&mut buf, 0x20, 3);
+        put_u32(&mut buf, 0x24, 0x5678);
+        put_u32(&mut buf, 0x40, STEPS_POS - OFFSET_BASE);
+        put_u32(&mut buf, 0x44, step_ids.len() as u32*step_size);
+        for (i, id) in step_ids.iter().enumerate() {
+            let step_pos = STEPS_POS + i as u32*step_size;
+            let link_pos = ids_pos + i as u32*8;
+            put_u32(&mut buf, step_pos, *id);
+            put_u32(&mut buf, step_pos + 4, 2);
+            put_u32(&mut buf, step_pos + 0x10, link_pos - OFFSET_BASE);
+            put_u32(&mut buf, step_pos + 0x14, 4);
+            put_u32(&mut buf, step_pos + 0x20, link_pos + 4 - OFFSET_BASE);
+            put_u32(&mut buf, step_pos + 0x24, 4);
+            put_u32(&mut buf, link_pos, id + 1);
+            put_u32(&mut buf, link_pos + 4, *id);
+        }
+        buf
+    }
+
+    #[test]
+    fn header_and_steps_are_read() {
+        let mut qst = Qst::new();
+        qst.read_file("test.qst", &mut SnoReader::new(file(&[10, 11]), None), None).unwrap();
+        let file = &qst.files["test.qst"];
+        assert_eq!((file.hash_id, file.quest_type, file.string_list.id), (0x1234, 3, 0x5678));
+        assert_eq!(file.steps.len(), 2);
+        for (step, id) in file.steps.iter().zip([10, 11]) {
+            assert_eq!((step.id, step.step_type), (id, 2));
+            assert_eq!(step.links, [id + 1]);
+            assert_eq!(step.objectives, [id]);
+        }
+    }
+
+    #[test]
+    fn dot_labels_are_escaped() {
+        let mut qst = Qst::new();
+        qst.new_file("test.qst", 1, 0, 0);
+        qst.files.get_mut("test.qst").unwrap().name = Some("a \\ \"quoted\" name".to_owned());
+        assert!(qst.to_dot().contains("label=\"a \\\\ \\\"quoted\\\" name\";"));
+    }
+}
@@ -1,51 +1,96 @@
-use std::{io::{self, Write, Seek}, fs::{File, self}, collections::HashMap};
-use egui::CollapsingHeader;
+use std::{io::{self, Write, Seek}, fs::File, collections::{HashMap, HashSet}};
+use egui::{CollapsingHeader, ComboBox, Color32, RichText};
 use itertools::Itertools;
 use serde::{Serialize, Deserialize};
 use serde_json;
 
-use super::{Parser};
-use crate::utils;
+use super::{self as parsers, Parser, Reveal, stl::{Stl, NAME_KEY}};
+use crate::{utils, game_build, raw::{self, RawRegion}, sno::SnoReader, toc::CoreToc, skill_tree::TreeView, planner::Build, skill_graph, skill_lint::{self, Issue}, query::{Query, Entry}, index::Index};
+
+/// power string lists are named after the power with this prefix
+const POWER_LIST_PREFIX: &str = "Power_";
+const MATCH_COLOR: Color32 = Color32::from_rgb(230, 80, 200);
 
 #[derive(Serialize, Deserialize)]
 pub struct Skl {
-    pub files: HashMap<String, SklFile>
+    pub files: HashMap<String, SklFile>,
+    #[serde(skip)]
+    view: SklView
+}
+
+/// what the search finds in one file
+enum FileMatch {
+    /// the file name matches, or there is no search
+    All,
+    Nodes(HashSet<u32>),
+    None
+}
+
+/// what the `.skl` tab shows: the list of nodes, or one file's tree on a canvas with a build planned on it
+#[derive(Default)]
+struct SklView {
+    show_tree: bool,
+    selected: Option<String>,
+    tree: TreeView,
+    build: Build,
+    /// build code being typed or last copied
+    code: String,
+    code_error: Option<String>,
+    /// integrity issues of the loaded files, checked when the tab is first drawn
+    issues: Option<Vec<Issue>>
+}
+
+impl SklView {
+    /// switches to another tree, starting with a fresh view and an empty build
+    fn select(&mut self, file_key: &str) {
+        if self.selected.as_deref() != Some(file_key) {
+            self.selected = Some(file_key.to_owned());
+            self.tree = Default::default();
+            self.build = Default::default();
+        }
+    }
 }
 
 impl Skl {
     pub fn new() -> Self {
         Self {
-            files: HashMap::new()
+            files: HashMap::new(),
+            view: Default::default()
         }
     }
 
-    fn add_field(&mut self, file_name: &str, value: SklField) {
-        let file = self.files.entry(file_name.to_string())
-            .or_insert(SklFile::new());
+    fn new_file(&mut self, file_name: &str, hash_id: u32) {
+        self.files.insert(file_name.to_owned(), SklFile::new(hash_id));
+    }
 
-        file.skills.push(value);
+    fn add_field(&mut self, file_name: &str, value: SklField) {
+        self.files.entry(file_name.to_string())
+            .and_modify(|k| {
+                k.skills.push(value);
+            });
     }
 
-    fn header(f: &mut File) -> io::Result<(u32, u32)> {
+    /// returns offset and length of the skill tree block and hash_id
+    fn header(f: &mut SnoReader) -> io::Result<(u32, u32, u32)> {
         let _deadbeef = utils::read_u32(f)?;
         let _file_type = utils::read_u32(f)?;
         utils::padding(f, 4)?;
-        let _hash_id = utils::read_u32(f)?;
+        let hash_id = utils::read_u32(f)?;
         utils::padding(f, 16)?; // end first line
         utils::padding(f, 32)?; // end second line
 
-        let skill_tree_offset = utils::read_u32(f)? + 0x10u32;
+        let skill_tree_offset = utils::read_ptr(f)?;
         let skill_tree_len = utils::read_u32(f)?;
         utils::padding(f, 8)?;
 
-        let _something_offset = utils::read_u32(f)? + 0x10u32;
-        let _something_len = utils::read_u32(f)?;
-        let _idk = utils::read_u32(f)?;
+        let _something_offset = utils::read_unknown(f)?;
+        let _something_len = utils::read_unknown(f)?;
+        let _idk = utils::read_unknown(f)?;
         
-        Ok((skill_tree_offset, skill_tree_len))
+        Ok((skill_tree_offset, skill_tree_len, hash_id))
     }
 
-    fn field(f: &mut File) -> io::Result<SklField> {
+    fn field(f: &mut SnoReader) -> io::Result<SklField> {
         let id = utils::read_u32(f)?;
         let reward_hash = utils::read_u32(f)?;
         utils::padding(f, 4)?;
@@ -54,7 +99,7 @@ impl Skl {
         let is_root = utils::read_u32(f)? == 1u32;
         let req_points = utils::read_u32(f)?;
         utils::padding(f, 12)?;
-        let connection_offset = utils::read_u32(f)? + 0x10u32;
+        let connection_offset = utils::read_ptr(f)?;
         let connection_len = utils::read_u32(f)?;
         let connections = Skl::get_connections(f, connection_offset, connection_len)?;
         utils::padding(f, 16)?;
@@ -64,7 +109,7 @@ impl Skl {
         Ok(skl_field)
     }
 
-    fn get_connections(f: &mut File, offset: u32, len: u32) -> io::Result<Vec<u32>> {
+    fn get_connections(f: &mut SnoReader, offset: u32, len: u32) -> io::Result<Vec<u32>> {
         let mut connections = Vec::new();
         let old_pos = f.stream_position()?;
         utils::go_to(f, offset.into())?;
@@ -80,45 +125,84 @@ impl Skl {
         Ok(connections)
     }
 
-    pub fn run(&mut self, path: String) -> io::Result<()> {
-        let dir = fs::read_dir(path)?;
-
-        for file in dir {
-            let f_u = file?;
-            let name = f_u.file_name();
-            let file_name = name.to_str().unwrap();
-
-            let mut f = File::open(&f_u.path())?;
-            let (info_offset, info_len) = Skl::header(&mut f)?;
-            utils::go_to(&mut f, info_offset.into())?; // move to info offset
-            let num_fields = info_len/64;
-
-            for _ in 0..num_fields {
-                let skl_field = Skl::field(&mut f)?;
-                self.add_field(file_name, skl_field);
+    /// names the reward of every node from a previously written sno table of contents, using the
+    /// name in the power's string list when there is one
+    pub fn resolve_names(&mut self, toc: &CoreToc, stl: Option<&Stl>) {
+        for file in self.files.values_mut() {
+            for skill in file.skills.iter_mut() {
+                skill.reward_name = toc.get(skill.reward_hash).map(|entry| {
+                    let list_names = [format!("{}{}", POWER_LIST_PREFIX, entry.name), entry.name.to_owned()];
+                    stl.and_then(|x| list_names.iter().find_map(|name| x.files.get(&Stl::list_name(name))))
+                        .and_then(|x| x.fields.get(NAME_KEY))
+                        .cloned()
+                        .unwrap_or_else(|| entry.name.to_owned())
+                });
             }
         }
+    }
 
-        let json = serde_json::to_string_pretty(&self)?;
-        let mut log = File::create("skl.json")?;
-        log.write(json.as_bytes())?;
+    /// a node as a search entry, found by its id, its reward hash or its power name
+    fn node_entry<'a>(file_name: &'a str, file: &SklFile, skill: &'a SklField) -> Entry<'a> {
+        Entry::new(file_name, vec![file.hash_id, skill.reward_hash])
+            .field("id", skill.id.to_string())
+            .field_opt("reward", skill.reward_name.as_ref())
+            .field("req_points", skill.req_points.to_string())
+    }
 
-        Ok(())
+    fn file_match(file_key: &str, file: &SklFile, query: &Query) -> FileMatch {
+        if query.is_empty() || query.matches_file(file_key) {
+            return FileMatch::All;
+        }
+
+        let nodes: HashSet<u32> = file.skills.iter()
+            .filter(|x| query.matches(&Skl::node_entry(file_key, file, x)))
+            .map(|x| x.id)
+            .collect();
+        match nodes.is_empty() {
+            true => FileMatch::None,
+            false => FileMatch::Nodes(nodes)
+        }
     }
-}
 
-impl Parser for Skl {
-    fn data_view(&self, ui: &mut egui::Ui, _filter: &str) { // TODO: Add filtering
-        let files = &self.files;
+    /// lists the files the search finds, files found by their nodes are opened with the matching
+    /// nodes expanded and highlighted, the revealed node is scrolled to
+    fn list_view(files: &HashMap<String, SklFile>, ui: &mut egui::Ui, query: &Query, reveal: Option<&Reveal>) {
         egui::Grid::new("aff_grid")
             .show(ui, |ui| {                        
                 for file_key in files.keys().sorted() {
                     let skills = &files[file_key];
-                    ui.collapsing(file_key, |ui| {
+                    let nodes = match Skl::file_match(file_key, skills, query) {
+                        FileMatch::All => None,
+                        FileMatch::Nodes(nodes) => Some(nodes),
+                        FileMatch::None => continue
+                    };
+                    let revealed = reveal.filter(|x| x.is_file(file_key));
+                    // scrolled to before the nodes, so a revealed node takes over the scroll
+                    if revealed.is_some() {
+                        ui.scroll_to_cursor(Some(egui::Align::TOP));
+                    }
+                    CollapsingHeader::new(file_key).open((revealed.is_some() || nodes.is_some()).then_some(true)).show(ui, |ui| {
                         for skill in skills.skills.iter() {
-                            let skill_header = CollapsingHeader::new(format!("id: {}", skill.id))
-                                .default_open(true);
-                            skill_header.show(ui, |ui| {
+                            let found = nodes.as_ref().map(|x| x.contains(&skill.id));
+                            let node_revealed = revealed.is_some_and(|x| x.is_entry(&Skl::node_entry(file_key, skills, skill)));
+                            let title = match found {
+                                Some(true) => RichText::new(format!("id: {}", skill.id)).strong().color(MATCH_COLOR),
+                                _ => RichText::new(format!("id: {}", skill.id))
+                            };
+                            let skill_header = CollapsingHeader::new(title)
+                                .id_source(format!("id: {}", skill.id))
+                                .default_open(true)
+                                .open(match node_revealed {
+                                    true => Some(true),
+                                    false => found
+                                });
+                            let response = skill_header.show(ui, |ui| {
+                                if let Some(name) = &skill.reward_name {
+                                    ui.horizontal(|h| {
+                                        h.strong("reward:");
+                                        h.label(name);
+                                    });
+                                }
                                 ui.horizontal(|h| {
                                     h.strong("req_points:");
                                     h.label(format!("{}", skill.req_points));
@@ -148,12 +232,149 @@ impl Parser for Skl {
                                     });
                                 });
                             });
+                            if node_revealed {
+                                response.header_response.scroll_to_me(Some(egui::Align::Center));
+                            }
                         }
                     });
                     ui.end_row();
                 }
             });
     }
+}
+
+impl Parser for Skl {
+    fn run(&mut self, path: String) -> io::Result<()> {
+        parsers::read_folder(self, &path)?;
+        self.finish()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if let Ok(toc) = CoreToc::load("coretoc.json") {
+            self.resolve_names(&toc, Stl::load("stl.json").ok().as_ref());
+        }
+
+        let json = serde_json::to_string_pretty(&self)?;
+        let mut log = File::create("skl.json")?;
+        log.write_all(json.as_bytes())?;
+        raw::write_report("skl", self.files.values().map(|x| &x.raw))?;
+        skill_graph::write_all(self)?;
+        skill_lint::write_report(self)?;
+
+        Ok(())
+    }
+
+    fn read_file(&mut self, file_name: &str, f: &mut SnoReader, build: Option<u32>) -> io::Result<()> {
+        let records = game_build::records(build);
+        let (info_offset, info_len, hash_id) = Skl::header(f)?;
+        self.new_file(file_name, hash_id);
+        let num_fields = info_len/records.skl_node;
+
+        for i in 0..num_fields {
+            utils::go_to(f, utils::record_pos(info_offset, i, records.skl_node)?)?;
+            f.begin("node");
+            let skl_field = Skl::field(f)?;
+            self.add_field(file_name, skl_field);
+        }
+        self.files.get_mut(file_name).unwrap().raw = f.take_raw();
+
+        Ok(())
+    }
+
+    fn data_view(&mut self, ui: &mut egui::Ui, query: &Query, reveal: Option<&Reveal>) {
+        let Self { files, view } = self;
+        if let Some(reveal) = reveal.filter(|_| view.show_tree) {
+            view.select(&reveal.file);
+        }
+        ui.horizontal(|h| {
+            h.selectable_value(&mut view.show_tree, false, "list");
+            h.selectable_value(&mut view.show_tree, true, "tree");
+            if view.show_tree {
+                ComboBox::from_id_source("skl_tree_file")
+                    .selected_text(view.selected.as_deref().unwrap_or("pick a file"))
+                    .show_ui(h, |ui| {
+                        for file_key in files.keys().filter(|x| !matches!(Skl::file_match(x, &files[*x], query), FileMatch::None)).sorted() {
+                            if ui.selectable_label(view.selected.as_ref() == Some(file_key), file_key).clicked() {
+                                view.select(file_key);
+                            }
+                        }
+                    });
+            }
+        });
+
+        if !view.show_tree {
+            ui.separator();
+            let issues = view.issues.get_or_insert_with(|| skill_lint::lint(files));
+            if !issues.is_empty() {
+                ui.collapsing(format!("{} integrity issues", issues.len()), |ui| {
+                    for issue in issues.iter() {
+                        ui.colored_label(Color32::from_rgb(220, 150, 50), format!("{}: {}", issue.file, issue.message));
+                    }
+                });
+            }
+            Skl::list_view(files, ui, query, reveal);
+            return;
+        }
+        let file = match view.selected.as_ref().and_then(|x| files.get(x)) {
+            Some(file) => file,
+            None => {
+                ui.separator();
+                ui.label("Pick a file to draw its tree");
+                return;
+            }
+        };
+
+        let errors = view.build.validate(file);
+        ui.horizontal(|h| {
+            h.label(format!("points: {}", view.build.total()));
+            if h.button("reset").clicked() {
+                view.build = Default::default();
+            }
+            h.add(egui::TextEdit::singleline(&mut view.code)
+                .hint_text("build code")
+                .desired_width(220f32));
+            if h.button("copy").clicked() {
+                view.code = view.build.to_code(file);
+                h.output_mut(|x| x.copied_text = view.code.to_owned());
+            }
+            if h.button("load").clicked() {
+                view.code_error = match Build::from_code(&view.code) {
+                    Ok((hash_id, build)) => match files.iter().find(|x| x.1.hash_id == hash_id) {
+                        Some((file_key, _)) => {
+                            view.select(file_key);
+                            view.build = build;
+                            None
+                        },
+                        None => Some(format!("no tree with hash_id {} is loaded", hash_id))
+                    },
+                    Err(e) => Some(e.to_string())
+                };
+            }
+            if let Some(error) = &view.code_error {
+                h.colored_label(Color32::RED, error);
+            }
+        });
+        if !errors.is_empty() {
+            ui.collapsing(format!("invalid build: {} problems", errors.len()), |ui| {
+                for error in errors.iter() {
+                    ui.colored_label(Color32::RED, error.to_string());
+                }
+            });
+        }
+        ui.separator();
+
+        let invalid = errors.iter().map(|x| x.node()).collect();
+        let found = match Skl::file_match(view.selected.as_deref().unwrap_or_default(), file, query) {
+            FileMatch::Nodes(nodes) => nodes,
+            _ => HashSet::new()
+        };
+        if let Some(click) = view.tree.show(ui, file, &view.build, &invalid, &found) {
+            match click.add {
+                true => view.build.add(click.id),
+                false => view.build.remove(click.id)
+            }
+        }
+    }
 
     fn load_data_file(&self) -> Option<Box<dyn Parser>> {
         match utils::load_or_pick_data_file("skl.json") {
@@ -169,19 +390,40 @@ impl Parser for Skl {
     fn tab_title(&self) -> String {
         ".skl".to_owned()
     }
+
+    fn hash_ids(&self) -> Vec<(String, u32)> {
+        self.files.iter().map(|(k, v)| (k.to_owned(), v.hash_id)).collect()
+    }
+
+    fn entries(&self) -> Vec<Entry<'_>> {
+        self.files.iter()
+            .flat_map(|(k, v)| v.skills.iter().map(move |x| Skl::node_entry(k, v, x)))
+            .collect()
+    }
+
+    fn index(&self) -> Option<&Index> {
+        None
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct SklFile {
+    #[serde(default)]
+    pub hash_id: u32,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     #[serde(default)]
-    pub skills: Vec<SklField>
+    pub skills: Vec<SklField>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub raw: Vec<RawRegion>
 }
 
 impl SklFile {
-    fn new() -> Self {
+    fn new(hash_id: u32) -> Self {
         Self {
-            skills: Vec::new()
+            hash_id,
+            skills: Vec::new(),
+            raw: Vec::new()
         }
     }
 }
@@ -196,7 +438,11 @@ pub struct SklField {
     pub y:f32,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     #[serde(default)]
-    pub connections: Vec<u32>
+    pub connections: Vec<u32>,
+    /// name of the power the node grants, when a sno table of contents was available
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub reward_name: Option<String>
 }
 
 impl SklField {
@@ -208,7 +454,41 @@ impl SklField {
             is_root,
             x,
             y,
-            connections
+            connections,
+            reward_name: None
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parsers::stl::StlFile, toc::SnoEntry};
+
+    use super::*;
+
+    #[test]
+    fn reward_names_come_from_the_power_string_list() {
+        let mut skl = Skl::new();
+        skl.files.insert("Tree.skl".to_owned(), SklFile {
+            hash_id: 1,
+            skills: vec![SklField::new(1, 0, 10, true, 0f32, 0f32, Vec::new()), SklField::new(2, 0, 20, false, 0f32, 0f32, Vec::new())],
+            raw: Vec::new()
+        });
+        let toc = CoreToc {
+            entries: HashMap::from([
+                (10, SnoEntry { group: 0, id: 10, name: "Sorcerer_Fireball".to_owned() }),
+                (20, SnoEntry { group: 0, id: 20, name: "Sorcerer_Unnamed".to_owned() })
+            ])
+        };
+        let mut stl = Stl::new();
+        stl.files.insert("Power_Sorcerer_Fireball.stl".to_owned(), StlFile {
+            hash_id: 5,
+            fields: HashMap::from([(NAME_KEY.to_owned(), "Fireball".to_owned())]),
+            raw: Vec::new()
+        });
+
+        skl.resolve_names(&toc, Some(&stl));
+        let names: Vec<_> = skl.files["Tree.skl"].skills.iter().map(|x| x.reward_name.as_deref()).collect();
+        assert_eq!(names, [Some("Fireball"), Some("Sorcerer_Unnamed")]);
+    }
+}
@@ -1,20 +1,39 @@
-use std::{io::{self, Write}, fs::{File, self}, collections::HashMap};
+use std::{io::{self, Write}, fs::{File, self}, collections::HashMap, path::Path, sync::Arc};
 use itertools::Itertools;
 use serde::{Serialize, Deserialize};
 use serde_json;
 
-use super::{Parser};
-use crate::utils;
+use super::{self as parsers, Parser, Reveal};
+use crate::{utils, game_build, raw::{self, RawRegion}, sno::SnoReader, query::{Query, Entry}, index::{Index, SearchIndex}, table::{TableView, Row}};
+
+const DEADBEEF: u32 = 0xDEADBEEF;
+/// size of the header written when there is no original file to take it from
+const HEADER_SIZE: usize = 0x30;
+/// offsets stored in a file without payload are relative to the end of its first line
+const OFFSET_BASE: usize = 0x10;
+const STRING_ALIGNMENT: usize = 8;
+/// keys of the strings naming and describing the sno file a string list belongs to
+pub const NAME_KEY: &str = "Name";
+pub const DESCRIPTION_KEY: &str = "Description";
+
+/// the bytes of each pair record with the key it holds
+type PairRecords = Vec<(String, Vec<u8>)>;
 
 #[derive(Serialize, Deserialize)]
 pub struct Stl {
-    pub files: HashMap<String, StlFile>
+    pub files: HashMap<String, StlFile>,
+    #[serde(skip)]
+    search: SearchIndex,
+    #[serde(skip)]
+    table: TableView
 }
 
 impl Stl {
     pub fn new() -> Self {
         Self {
-            files: HashMap::new()
+            files: HashMap::new(),
+            search: SearchIndex::default(),
+            table: TableView::default()
         }
     }
 
@@ -22,36 +41,39 @@ impl Stl {
         self.files.insert(file_name.to_owned(), StlFile::new(hash_id));
     }
 
-    fn add_field(&mut self, file_name: &String, key: String, value: String) {
+    fn add_field(&mut self, file_name: &str, key: String, value: String) {
         self.files.entry(file_name.to_string())
             .and_modify(|k| { 
                 k.fields.insert(key, value);
             });
     }
     
-    /// returns length of the info block and hash_id
-    fn header(f: &mut File) -> io::Result<(u32, u32)> {
+    /// returns offset and length of the info block and hash_id
+    ///
+    /// single files point the offset just past the header, split files into the payload
+    fn header(f: &mut SnoReader) -> io::Result<(u32, u32, u32)> {
         let _deadbeef = utils::read_u32(f)?;
         utils::padding(f, 8)?;
 
         let hash_id = utils::read_u32(f)?;
-        utils::padding(f, 20)?;
+        utils::padding(f, 16)?;
 
+        let info_offset = utils::read_ptr(f)?;
         let info_len = utils::read_u32(f)?;
         utils::padding(f, 8)?;
         
-        Ok((info_len, hash_id))
+        Ok((info_offset, info_len, hash_id))
     }
 
-    fn info(f: &mut File) -> io::Result<(String, String)> {
+    fn info(f: &mut SnoReader) -> io::Result<(String, String)> {
         utils::padding(f, 8)?;
-        let key_offset = utils::read_u32(f)? + 0x10u32;
+        let key_offset = utils::read_ptr(f)?;
         let key_len = utils::read_u32(f)?;
         let buf = utils::read_offset(f, key_offset as u64, key_len as usize)?;
         let key_string = String::from_utf8(buf).unwrap();
         utils::padding(f, 8)?;
     
-        let val_offset = utils::read_u32(f)? + 0x10u32;
+        let val_offset = utils::read_ptr(f)?;
         let val_len = utils::read_u32(f)?;
         let buf = utils::read_offset(f, val_offset as u64, val_len as usize)?;
         let val_string = String::from_utf8(buf).unwrap();
@@ -60,37 +82,232 @@ impl Stl {
         Ok((key_string, val_string))
     }
 
-    pub fn run(&mut self, path: String) -> io::Result<()> {
-        let dir = fs::read_dir(path)?;
+    /// name of the string list holding the text for another sno file, which shares its file stem
+    pub fn list_name(file_name: &str) -> String {
+        let stem = Path::new(file_name).file_stem().and_then(|x| x.to_str()).unwrap_or(file_name);
+        format!("{}.stl", stem)
+    }
+
+    /// file names of the string lists by their hash_id, the sno id other files refer to them by
+    pub fn ids(&self) -> HashMap<u32, &str> {
+        self.files.iter().map(|(k, v)| (v.hash_id, k.as_str())).collect()
+    }
+
+    /// loads a previously written stl json dump without prompting
+    pub fn load(path: &str) -> io::Result<Self> {
+        let buf = utils::read_file(path)?;
+        let stl = serde_json::from_slice(&buf)?;
+
+        Ok(stl)
+    }
+
+    /// writes every file back to an `.stl` binary in `out_path`, starting from the file of the same
+    /// name in `template_path` when there is one, with the records of the template's game build
+    pub fn write(&self, out_path: &str, template_path: Option<&str>) -> io::Result<()> {
+        let build = game_build::detect(Path::new(template_path.unwrap_or(out_path)));
+        fs::create_dir_all(out_path)?;
+        for (file_name, file) in self.files.iter() {
+            let template = match template_path.map(|x| Path::new(x).join(file_name)) {
+                Some(path) if path.is_file() => Some(fs::read(path)?),
+                _ => None
+            };
+            let buf = Stl::write_file(file_name, file, template.as_deref(), build)?;
+            let mut f = File::create(Path::new(out_path).join(file_name))?;
+            f.write_all(&buf)?;
+        }
 
-        for file in dir {
-            let f_u = file?;
-            let file_name = f_u.file_name().to_str().unwrap().to_owned();
+        Ok(())
+    }
 
-            let mut f = File::open(f_u.path())?;
-            let (info_len, hash_id) = Stl::header(&mut f)?;
-            self.new_file(file_name.as_str(), hash_id);
-            
-            let num_pairs = info_len/40;
-            for _ in 0..num_pairs {
-                let (key, value) = Stl::info(&mut f)?;
-                self.add_field(&file_name, key.replace(char::from(0), ""), value.replace(char::from(0), ""));
+    /// serializes one string list, checking that parsing the result gives the same file back
+    ///
+    /// a template keeps its header and the unknown bytes of the records whose key is still there,
+    /// keys keep the template's order and new keys follow sorted. anything else the template's header
+    /// points to is not carried over
+    pub fn write_file(file_name: &str, file: &StlFile, template: Option<&[u8]>, build: Option<u32>) -> io::Result<Vec<u8>> {
+        let pair_size = game_build::records(build).stl_pair as usize;
+        let (mut buf, mut records) = match template {
+            Some(template) => Stl::template(template, pair_size)?,
+            None => {
+                let mut header = vec![0u8; HEADER_SIZE];
+                put_u32(&mut header, 0, DEADBEEF);
+                (header, Vec::new())
             }
+        };
+        records.retain(|(key, _)| file.fields.contains_key(key));
+        let known: Vec<String> = records.iter().map(|x| x.0.to_owned()).collect();
+        for key in file.fields.keys().filter(|x| !known.contains(x)).sorted() {
+            records.push((key.to_owned(), vec![0u8; pair_size]));
+        }
+
+        buf.resize(align(buf.len()), 0);
+        let table_pos = buf.len();
+        let strings_pos = table_pos + records.len()*pair_size;
+        let mut table = Vec::new();
+        let mut strings = Vec::new();
+        for (key, mut record) in records {
+            let (key_offset, key_len) = push_string(&mut strings, strings_pos, &key);
+            let (val_offset, val_len) = push_string(&mut strings, strings_pos, &file.fields[&key]);
+            put_u32(&mut record, 8, key_offset);
+            put_u32(&mut record, 12, key_len);
+            put_u32(&mut record, 24, val_offset);
+            put_u32(&mut record, 28, val_len);
+            table.extend(record);
         }
 
+        put_u32(&mut buf, 0xC, file.hash_id);
+        put_u32(&mut buf, 0x20, (table_pos - OFFSET_BASE) as u32);
+        put_u32(&mut buf, 0x24, table.len() as u32);
+        buf.extend(table);
+        buf.extend(strings);
+
+        let mut check = Stl::new();
+        check.read_file(file_name, &mut SnoReader::new(buf.clone(), None), build)?;
+        match check.files.get(file_name) {
+            Some(x) if x.hash_id == file.hash_id && x.fields == file.fields => Ok(buf),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} does not read back the same after writing", file_name)))
+        }
+    }
+
+    /// the header of an original file and its records by key, in file order
+    fn template(template: &[u8], pair_size: usize) -> io::Result<(Vec<u8>, PairRecords)> {
+        let mut f = SnoReader::new(template.to_vec(), None);
+        let (info_offset, info_len, _) = Stl::header(&mut f)?;
+        let info_offset = info_offset as usize;
+        if info_offset < HEADER_SIZE || info_offset > template.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "template string table does not follow its header"));
+        }
+
+        let mut records = Vec::new();
+        for i in 0..info_len as usize/pair_size {
+            let end = i.checked_mul(pair_size)
+                .and_then(|x| x.checked_add(info_offset))
+                .and_then(|x| x.checked_add(pair_size))
+                .filter(|x| *x <= template.len());
+            let pos = match end {
+                Some(end) => end - pair_size,
+                None => return Err(io::Error::new(io::ErrorKind::InvalidData, "template string table runs past the end of the file"))
+            };
+            utils::go_to(&mut f, pos as u64)?;
+            let (key, _) = Stl::info(&mut f)?;
+            records.push((key.replace(char::from(0), ""), template[pos..pos + pair_size].to_vec()));
+        }
+
+        Ok((template[..info_offset].to_vec(), records))
+    }
+
+    /// the files the search finds, sorted, answered by the index when it has the search
+    fn found_keys<'a>(files: &'a HashMap<String, StlFile>, search: &mut SearchIndex, query: &Query) -> Vec<&'a String> {
+        let found = search.files(query);
+        files.keys()
+            .filter(|x| match found {
+                Some(found) => found.contains(*x),
+                None => query.matches(&Stl::entry(x, &files[*x]))
+            })
+            .sorted()
+            .collect()
+    }
+
+    /// a row for every string of the files, in file and key order
+    fn table_rows(files: &HashMap<String, StlFile>, keys: &[&String]) -> Vec<Row> {
+        let mut rows = Vec::new();
+        for file_key in keys {
+            let file = &files[*file_key];
+            let file_name: Arc<str> = Arc::from(file_key.as_str());
+            for key in file.fields.keys().sorted() {
+                rows.push(Row {
+                    file: file_name.clone(),
+                    hash_id: file.hash_id,
+                    key: key.to_owned(),
+                    value: file.fields[key].to_owned()
+                });
+            }
+        }
+
+        rows
+    }
+
+    /// the file as a search entry, with every string
+    fn entry<'a>(file_name: &'a str, file: &'a StlFile) -> Entry<'a> {
+        file.fields.iter().fold(Entry::new(file_name, vec![file.hash_id]), |entry, (k, v)| entry.field(k.as_str(), v.as_str()))
+    }
+}
+
+/// appends a null terminated string, padded to the string alignment, returning its offset and length
+fn push_string(strings: &mut Vec<u8>, strings_pos: usize, text: &str) -> (u32, u32) {
+    let offset = strings_pos + strings.len() - OFFSET_BASE;
+    strings.extend(text.as_bytes());
+    strings.push(0);
+    let len = text.len() + 1;
+    strings.resize(align(strings.len()), 0);
+
+    (offset as u32, len as u32)
+}
+
+fn put_u32(buf: &mut [u8], pos: usize, value: u32) {
+    buf[pos..pos + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn align(len: usize) -> usize {
+    len.div_ceil(STRING_ALIGNMENT)*STRING_ALIGNMENT
+}
+
+impl Parser for Stl {
+    fn run(&mut self, path: String) -> io::Result<()> {
+        parsers::read_folder(self, &path)?;
+        self.finish()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
         let json = serde_json::to_string_pretty(&self)?;
         let mut log = File::create("stl.json")?;
-        log.write(json.as_bytes())?;
+        log.write_all(json.as_bytes())?;
+        raw::write_report("stl", self.files.values().map(|x| &x.raw))?;
+        let index = Index::load_or_build("stl.json", &self.entries());
+        self.search = SearchIndex::new(index);
 
         Ok(())
     }
-}
 
-impl Parser for Stl {
-    fn data_view(&self, ui: &mut egui::Ui, filter: &str) {
+    fn read_file(&mut self, file_name: &str, f: &mut SnoReader, build: Option<u32>) -> io::Result<()> {
+        let records = game_build::records(build);
+        let (info_offset, info_len, hash_id) = Stl::header(f)?;
+        self.new_file(file_name, hash_id);
+
+        let num_pairs = info_len/records.stl_pair;
+        for i in 0..num_pairs {
+            utils::go_to(f, utils::record_pos(info_offset, i, records.stl_pair)?)?;
+            f.begin("pair");
+            let (key, value) = Stl::info(f)?;
+            self.add_field(file_name, key.replace(char::from(0), ""), value.replace(char::from(0), ""));
+        }
+        self.files.get_mut(file_name).unwrap().raw = f.take_raw();
+
+        Ok(())
+    }
+
+    fn data_view(&mut self, ui: &mut egui::Ui, query: &Query, reveal: Option<&Reveal>) {
+        if reveal.is_some() {
+            self.table.show = false;
+        }
+        ui.horizontal(|h| {
+            h.selectable_value(&mut self.table.show, false, "list");
+            h.selectable_value(&mut self.table.show, true, "table");
+        });
         let files = &self.files;
-        let keys: Vec<_> =  files.keys().filter(|x| filter.is_empty() || x.to_lowercase().contains(filter)).sorted().collect();
-        let scroll = egui::ScrollArea::new([true, true]);
+        if self.table.show {
+            // the rows are only collected again when the search changed
+            if self.table.is_stale(query) {
+                self.table.set_rows(query, Stl::table_rows(files, &Stl::found_keys(files, &mut self.search, query)));
+            }
+            self.table.show(ui, "value");
+            return;
+        }
+        let keys = Stl::found_keys(files, &mut self.search, query);
+        let mut scroll = egui::ScrollArea::new([true, true]);
+        if let Some(index) = reveal.and_then(|x| keys.iter().position(|key| x.is_file(key))) {
+            scroll = scroll.vertical_scroll_offset(index as f32*(10f32 + ui.spacing().item_spacing.y));
+        }
         scroll.show_rows(ui, 
             10f32, 
             keys.len(), 
@@ -102,15 +319,17 @@ impl Parser for Stl {
                             false => row_range
                         };
                         for item in &keys[range] {
+                            let revealed = reveal.is_some_and(|x| x.is_file(item));
                             if files[*item].fields.len() > 0 {
-                                ui.collapsing(*item, |ui| {
+                                parsers::file_header(ui, *item, revealed, |ui| {
                                     ui.horizontal(|h| {
                                         h.strong("hash_id:");
                                         h.label(format!("{} ({:X})", files[*item].hash_id, files[*item].hash_id));
                                     });
                                     let values = &files[*item];
                                     for value in values.fields.keys().sorted() {
-                                        ui.horizontal(|ui| {
+                                        let field = reveal.is_some_and(|x| x.is_field(item, value, &values.fields[value]));
+                                        parsers::field_row(ui, field, |ui| {
                                             ui.strong(format!("{}:", value));
                                             ui.label(&values.fields[value]);
                                         });
@@ -118,7 +337,7 @@ impl Parser for Stl {
                                 });
                             }
                             else {
-                                ui.collapsing(*item, |ui| {
+                                parsers::file_header(ui, *item, revealed, |ui| {
                                     ui.horizontal(|h| {
                                         h.strong("hash_id:");
                                         h.label(format!("{}", files[*item].hash_id));
@@ -133,10 +352,11 @@ impl Parser for Stl {
     }
 
     fn load_data_file(&self) -> Option<Box<dyn Parser>> {
-        match utils::load_or_pick_data_file("stl.json") {
-            Some(buf) => {
-                let data_str = String::from_utf8(buf).unwrap();
-                let stl: Stl = serde_json::from_str(&data_str).unwrap();
+        match utils::data_file_path("stl.json") {
+            Some(path) => {
+                let mut stl = Stl::load(&path).unwrap();
+                let index = Index::load_or_build(&path, &stl.entries());
+                stl.search = SearchIndex::new(index);
                 Some(Box::new(stl) as Box<dyn Parser>)
             },
             _ => None
@@ -146,6 +366,63 @@ impl Parser for Stl {
     fn tab_title(&self) -> String {
         ".stl".to_owned()
     }
+
+    fn hash_ids(&self) -> Vec<(String, u32)> {
+        self.files.iter().map(|(k, v)| (k.to_owned(), v.hash_id)).collect()
+    }
+
+    fn entries(&self) -> Vec<Entry<'_>> {
+        self.files.iter().map(|(k, v)| Stl::entry(k, v)).collect()
+    }
+
+    fn index(&self) -> Option<&Index> {
+        self.search.index()
+    }
+}
+
+/// the string list another sno file takes its text from
+#[derive(Serialize, Deserialize, Default)]
+pub struct ListRef {
+    /// sno id of the string list stored in the file, 0 when it has none
+    #[serde(rename = "string_list_id")]
+    #[serde(default)]
+    pub id: u32,
+    /// the string list the reference resolved to
+    #[serde(rename = "string_list")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub file: Option<String>,
+    /// set when the file has no reference and the string list was picked by its file stem
+    #[serde(rename = "string_list_guessed")]
+    #[serde(skip_serializing_if = "is_false")]
+    #[serde(default)]
+    pub guessed: bool
+}
+
+impl ListRef {
+    pub fn new(id: u32) -> Self {
+        Self {
+            id,
+            file: None,
+            guessed: false
+        }
+    }
+
+    /// looks up the string list by its sno id, or by the file stem when there is no id, and remembers it
+    pub fn resolve<'a>(&mut self, file_name: &str, stl: &'a Stl, ids: &HashMap<u32, &str>) -> Option<&'a StlFile> {
+        let list = match self.id {
+            0 => Some(Stl::list_name(file_name)),
+            id => ids.get(&id).map(|x| x.to_string())
+        };
+        self.guessed = self.id == 0;
+        self.file = list.filter(|x| stl.files.contains_key(x));
+
+        self.file.as_ref().map(|x| &stl.files[x])
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
 }
 
 #[derive(Serialize, Deserialize)]
@@ -153,14 +430,113 @@ pub struct StlFile {
     pub hash_id: u32,
     #[serde(skip_serializing_if = "HashMap::is_empty")]
     #[serde(default)]
-    pub fields: HashMap<String, String>
+    pub fields: HashMap<String, String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub raw: Vec<RawRegion>
 }
 
 impl StlFile {
     fn new(hash_id: u32) -> Self {
         Self {
             hash_id,
-            fields: HashMap::new()
+            fields: HashMap::new(),
+            raw: Vec::new()
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(fields: &[(&str, &str)]) -> StlFile {
+        let mut file = StlFile::new(0x1234);
+        file.fields = fields.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        file
+    }
+
+    fn read(buf: &[u8]) -> StlFile {
+        let mut stl = Stl::new();
+        stl.read_file("test.stl", &mut SnoReader::new(buf.to_vec(), None), None).unwrap();
+        stl.files.remove("test.stl").unwrap()
+    }
+
+    /// position of the first pair record in a written file
+    fn table_pos(buf: &[u8]) -> usize {
+        u32::from_le_bytes(buf[0x20..0x24].try_into().unwrap()) as usize + OFFSET_BASE
+    }
+
+    #[test]
+    fn round_trip_without_template() {
+        // an empty value, and strings whose terminator does and does not end on the alignment
+        let original = file(&[("Name", "Fireball"), ("Empty", ""), ("Seven77", "abc"), ("Desc", "1234567")]);
+        let buf = Stl::write_file("test.stl", &original, None, None).unwrap();
+        assert_eq!(u32::from_le_bytes(buf[0..4].try_into().unwrap()), DEADBEEF);
+        assert_eq!(table_pos(buf.as_slice()) % STRING_ALIGNMENT, 0);
+
+        let read = read(&buf);
+        assert_eq!(read.hash_id, original.hash_id);
+        assert_eq!(read.fields, original.fields);
+    }
+
+    #[test]
+    fn round_trip_with_template() {
+        let mut template = Stl::write_file("test.stl", &file(&[("A", "first"), ("B", "second")]), None, None).unwrap();
+        // an unknown byte of the record for A, which has to survive the rewrite
+        let pos = table_pos(&template);
+        template[pos] = 0xAA;
+        template[0x4] = 0xBB;
+
+        let changed = file(&[("A", ""), ("C", "third")]);
+        let buf = Stl::write_file("test.stl", &changed, Some(&template), None).unwrap();
+        assert_eq!(buf[0x4], 0xBB);
+        assert_eq!(buf[table_pos(&buf)], 0xAA);
+
+        let read = read(&buf);
+        assert_eq!(read.fields, changed.fields);
+    }
+
+    #[test]
+    fn pairs_are_read_where_the_header_points() {
+        let original = file(&[("A", "first"), ("B", "second")]);
+        let buf = Stl::write_file("test.stl", &original, None, None).unwrap();
+        // a single file's pairs follow the header, where they were read from before the offset was
+        assert_eq!(table_pos(&buf), HEADER_SIZE);
+
+        // the pairs moved behind the strings, with junk left in their old place
+        let table_len = u32::from_le_bytes(buf[0x24..0x28].try_into().unwrap()) as usize;
+        let mut moved = buf.clone();
+        moved.extend_from_slice(&buf[HEADER_SIZE..HEADER_SIZE + table_len]);
+        moved[HEADER_SIZE..HEADER_SIZE + table_len].fill(0xFF);
+        put_u32(&mut moved, 0x20, (buf.len() - OFFSET_BASE) as u32);
+        assert_eq!(read(&moved).fields, original.fields);
+    }
+
+    #[test]
+    fn list_refs_resolve_by_id_then_by_stem() {
+        let mut stl = Stl::new();
+        stl.files.insert("Quest_A.stl".to_owned(), StlFile::new(7));
+        stl.files.insert("Other.stl".to_owned(), StlFile::new(9));
+        let ids = stl.ids();
+
+        let mut by_id = ListRef::new(9);
+        assert!(by_id.resolve("Quest_A.qst", &stl, &ids).is_some());
+        assert_eq!((by_id.file.as_deref(), by_id.guessed), (Some("Other.stl"), false));
+
+        let mut by_stem = ListRef::new(0);
+        assert!(by_stem.resolve("Quest_A.qst", &stl, &ids).is_some());
+        assert_eq!((by_stem.file.as_deref(), by_stem.guessed), (Some("Quest_A.stl"), true));
+
+        let mut missing = ListRef::new(8);
+        assert!(missing.resolve("Quest_A.qst", &stl, &ids).is_none());
+        assert_eq!(missing.file, None);
+    }
+
+    #[test]
+    fn truncated_template_is_an_error() {
+        let template = Stl::write_file("test.stl", &file(&[("A", "first"), ("B", "second")]), None, None).unwrap();
+        let truncated = &template[..table_pos(&template) + 8];
+        assert!(Stl::write_file("test.stl", &file(&[("A", "x")]), Some(truncated), None).is_err());
+    }
+}
@@ -0,0 +1,276 @@
+use std::{io, fmt, collections::{BTreeMap, HashMap, HashSet}};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+
+use crate::{skill_graph, parsers::skl::{SklFile, SklField}};
+
+/// first byte of a build code, bump it when the encoding changes
+const CODE_VERSION: u8 = 1;
+/// the skill tree does not store ranks, so every node takes up to this many points
+pub const MAX_NODE_POINTS: u32 = 5;
+
+/// points spent on the nodes of one skill tree, by node id
+#[derive(Default, Clone, PartialEq)]
+pub struct Build {
+    pub points: BTreeMap<u32, u32>
+}
+
+/// why a build could not be played
+pub enum BuildError {
+    UnknownNode(u32),
+    TooManyPoints { id: u32, points: u32 },
+    /// no root connects to the node through nodes with points
+    Unreachable(u32),
+    /// fewer points are spent below the node's gate than it requires
+    GateNotMet { id: u32, required: u32, spent: u32 }
+}
+
+impl BuildError {
+    pub fn node(&self) -> u32 {
+        match self {
+            BuildError::UnknownNode(id) | BuildError::Unreachable(id) => *id,
+            BuildError::TooManyPoints { id, .. } | BuildError::GateNotMet { id, .. } => *id
+        }
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::UnknownNode(id) => write!(f, "node {} is not in the tree", id),
+            BuildError::TooManyPoints { id, points } => write!(f, "node {} has {} points, at most {} fit", id, points, MAX_NODE_POINTS),
+            BuildError::Unreachable(id) => write!(f, "node {} is not connected to a root", id),
+            BuildError::GateNotMet { id, required, spent } => write!(f, "node {} needs {} points spent before it, {} are", id, required, spent)
+        }
+    }
+}
+
+impl Build {
+    pub fn total(&self) -> u32 {
+        self.points.values().sum()
+    }
+
+    pub fn get(&self, id: u32) -> u32 {
+        self.points.get(&id).copied().unwrap_or(0)
+    }
+
+    /// adds a point to a node, up to its maximum
+    pub fn add(&mut self, id: u32) {
+        let points = self.points.entry(id).or_default();
+        *points = (*points + 1).min(MAX_NODE_POINTS);
+    }
+
+    pub fn remove(&mut self, id: u32) {
+        if let Some(points) = self.points.get_mut(&id) {
+            *points = points.saturating_sub(1);
+            if *points == 0 {
+                self.points.remove(&id);
+            }
+        }
+    }
+
+    /// checks every node with points against the tree: it has to exist, be reachable from a root through
+    /// roots and nodes with points, and have at least its `req_points` spent on nodes with a lower gate
+    pub fn validate(&self, file: &SklFile) -> Vec<BuildError> {
+        let nodes: HashMap<u32, &SklField> = file.skills.iter().map(|x| (x.id, x)).collect();
+        let mut errors = Vec::new();
+        for (id, points) in self.points.iter() {
+            if !nodes.contains_key(id) {
+                errors.push(BuildError::UnknownNode(*id));
+            }
+            else if *points > MAX_NODE_POINTS {
+                errors.push(BuildError::TooManyPoints { id: *id, points: *points });
+            }
+        }
+
+        let reachable = self.reachable(file);
+        for (id, node) in self.points.keys().filter_map(|x| nodes.get(x).map(|node| (*x, node))) {
+            if !reachable.contains(&id) {
+                errors.push(BuildError::Unreachable(id));
+            }
+            let spent = self.points.iter()
+                .filter(|(other, _)| nodes.get(other).map(|x| x.req_points < node.req_points).unwrap_or(false))
+                .map(|x| x.1)
+                .sum();
+            if spent < node.req_points {
+                errors.push(BuildError::GateNotMet { id, required: node.req_points, spent });
+            }
+        }
+
+        errors
+    }
+
+    /// nodes connected to a root through roots and nodes with points, connections count both ways
+    fn reachable(&self, file: &SklFile) -> HashSet<u32> {
+        skill_graph::root_depths(file, |x| self.points.contains_key(&x)).into_keys().collect()
+    }
+
+    /// a url safe string holding the tree's hash_id and the points of every node
+    pub fn to_code(&self, file: &SklFile) -> String {
+        let mut buf = vec![CODE_VERSION];
+        buf.extend(file.hash_id.to_le_bytes());
+        for (id, points) in self.points.iter() {
+            write_varint(&mut buf, *id);
+            buf.push(*points as u8);
+        }
+
+        URL_SAFE_NO_PAD.encode(buf)
+    }
+
+    /// reads a build code, returning the hash_id of the tree it was made for with the build
+    pub fn from_code(code: &str) -> io::Result<(u32, Build)> {
+        let buf = URL_SAFE_NO_PAD.decode(code.trim()).map_err(|x| invalid(&x.to_string()))?;
+        match buf.first() {
+            Some(&CODE_VERSION) => (),
+            Some(version) => return Err(invalid(&format!("unknown build code version {}", version))),
+            None => return Err(invalid("empty build code"))
+        }
+        let hash_id = buf.get(1..5)
+            .map(|x| u32::from_le_bytes(x.try_into().unwrap()))
+            .ok_or_else(|| invalid("build code too short"))?;
+
+        let mut build = Build::default();
+        let mut pos = 5;
+        while pos < buf.len() {
+            let id = read_varint(&buf, &mut pos)?;
+            let points = *buf.get(pos).ok_or_else(|| invalid("build code ends inside a node"))?;
+            pos += 1;
+            if points == 0 {
+                return Err(invalid(&format!("node {} has no points in build code", id)));
+            }
+            if build.points.insert(id, points as u32).is_some() {
+                return Err(invalid(&format!("node {} appears twice in build code", id)));
+            }
+        }
+
+        Ok((hash_id, build))
+    }
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn read_varint(buf: &[u8], pos: &mut usize) -> io::Result<u32> {
+    let mut value = 0u32;
+    for shift in (0..35).step_by(7) {
+        let byte = *buf.get(*pos).ok_or_else(|| invalid("build code ends inside a node"))?;
+        *pos += 1;
+        value |= ((byte & 0x7F) as u32).checked_shl(shift).unwrap_or(0);
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(invalid("node id too long in build code"))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: u32, req_points: u32, is_root: bool, connections: &[u32]) -> SklField {
+        SklField {
+            id,
+            req_points,
+            reward_hash: 0,
+            is_root,
+            x: 0f32,
+            y: 0f32,
+            connections: connections.to_vec(),
+            reward_name: None
+        }
+    }
+
+    /// a root leading to 2, which leads to the gated 3; 4 hangs off 3 and 5 is on its own
+    fn tree() -> SklFile {
+        SklFile {
+            hash_id: 0xABCD,
+            skills: vec![node(1, 0, true, &[2]), node(2, 0, false, &[3]), node(3, 2, false, &[4]), node(4, 2, false, &[]), node(5, 0, false, &[])],
+            raw: Vec::new()
+        }
+    }
+
+    fn build(points: &[(u32, u32)]) -> Build {
+        Build { points: points.iter().copied().collect() }
+    }
+
+    fn errors(build: &Build) -> Vec<String> {
+        build.validate(&tree()).iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn valid_build_has_no_errors() {
+        assert!(errors(&build(&[(2, 2), (3, 1), (4, 1)])).is_empty());
+    }
+
+    #[test]
+    fn unreachable_nodes_are_reported() {
+        assert_eq!(errors(&build(&[(5, 1)])), ["node 5 is not connected to a root"]);
+        // 4 connects back to 3 only, which has no points
+        assert_eq!(errors(&build(&[(2, 2), (4, 1)])), ["node 4 is not connected to a root"]);
+    }
+
+    #[test]
+    fn unmet_gates_are_reported() {
+        assert_eq!(errors(&build(&[(2, 1), (3, 1)])), ["node 3 needs 2 points spent before it, 1 are"]);
+    }
+
+    #[test]
+    fn too_many_points_and_unknown_nodes_are_reported() {
+        assert_eq!(errors(&build(&[(2, 6)])), ["node 2 has 6 points, at most 5 fit"]);
+        assert_eq!(errors(&build(&[(9, 1)])), ["node 9 is not in the tree"]);
+    }
+
+    #[test]
+    fn points_stay_within_their_range() {
+        let mut build = Build::default();
+        for _ in 0..MAX_NODE_POINTS + 2 {
+            build.add(2);
+        }
+        assert_eq!(build.get(2), MAX_NODE_POINTS);
+
+        build.points.insert(3, 0);
+        build.remove(3);
+        build.remove(4);
+        assert_eq!(build.get(3), 0);
+        assert!(!build.points.contains_key(&3));
+    }
+
+    #[test]
+    fn codes_round_trip() {
+        let original = build(&[(2, 2), (3, 1), (300, 5), (u32::MAX, 1)]);
+        let (hash_id, read) = Build::from_code(&original.to_code(&tree())).unwrap();
+        assert_eq!(hash_id, 0xABCD);
+        assert!(read == original);
+    }
+
+    #[test]
+    fn bad_codes_are_errors() {
+        let code = URL_SAFE_NO_PAD.decode(build(&[(300, 2)]).to_code(&tree())).unwrap();
+        for truncated in [0, 3, code.len() - 1, code.len() - 2] {
+            assert!(Build::from_code(&URL_SAFE_NO_PAD.encode(&code[..truncated])).is_err(), "{} bytes read", truncated);
+        }
+
+        let mut wrong_version = code.clone();
+        wrong_version[0] = CODE_VERSION + 1;
+        assert!(Build::from_code(&URL_SAFE_NO_PAD.encode(wrong_version)).is_err());
+
+        let mut no_points = code.clone();
+        *no_points.last_mut().unwrap() = 0;
+        assert!(Build::from_code(&URL_SAFE_NO_PAD.encode(no_points)).is_err());
+
+        let mut duplicate = code.clone();
+        duplicate.extend_from_within(5..);
+        assert!(Build::from_code(&URL_SAFE_NO_PAD.encode(duplicate)).is_err());
+
+        assert!(Build::from_code("not base64!").is_err());
+    }
+}
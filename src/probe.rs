@@ -0,0 +1,231 @@
+use std::{io::{self, Write}, fs::{self, File}, collections::HashMap, path::Path};
+use itertools::Itertools;
+
+use crate::{sno::SnoReader, layout::{Layouts, Layout, Field, FieldKind}};
+
+/// offsets in the header start after the deadbeef, file type, padding and sno id line
+const HEADER_START: u32 = 0x10;
+const HASH_ID_OFFSET: u32 = 0xC;
+/// share of the files a guess has to hold in to make it into the draft
+const MIN_SHARE: f32 = 0.9;
+/// how many pointer levels are followed below the header
+const MAX_DEPTH: u32 = 2;
+
+/// what the 8 bytes at a position look like in one file
+enum Pair {
+    /// offset and length of a region inside the file
+    Valid(u32, u32),
+    /// a zero length, which fits any offset
+    Empty,
+    Invalid
+}
+
+/// every region one candidate pair points at, across the probed files
+struct Targets<'a> {
+    regions: Vec<(&'a SnoReader, u32, u32)>
+}
+
+/// guesses a layout for the most common extension in a folder and writes it to `<extension>_probe.toml`
+pub fn run(path: &str) -> io::Result<()> {
+    let mut paths: HashMap<String, Vec<_>> = HashMap::new();
+    for file in fs::read_dir(path)? {
+        let f_u = file?;
+        if let Some(extension) = f_u.path().extension().and_then(|x| x.to_str()) {
+            paths.entry(extension.to_owned()).or_default().push(f_u.path());
+        }
+    }
+    let (extension, paths) = match paths.into_iter().max_by_key(|x| x.1.len()) {
+        Some(most_common) => most_common,
+        None => return Err(io::Error::new(io::ErrorKind::NotFound, format!("no files to probe in {}", path)))
+    };
+
+    let files = paths.iter().map(|x| SnoReader::open(x)).collect::<io::Result<Vec<_>>>()?;
+    let layout = probe(&extension, &files);
+
+    let draft = toml::to_string(&Layouts { layouts: vec![layout] })
+        .map_err(|x| io::Error::new(io::ErrorKind::InvalidData, x.to_string()))?;
+    let mut log = File::create(Path::new(&format!("{}_probe.toml", extension)))?;
+    log.write_all(draft.as_bytes())?;
+
+    Ok(())
+}
+
+/// finds the header fields shared by the files: offset and length pairs pointing inside the file,
+/// with the arrays or strings they point to, floats, and other non zero words
+pub fn probe(extension: &str, files: &[SnoReader]) -> Layout {
+    let mut layout = Layout {
+        extension: extension.to_owned(),
+        min_build: None,
+        max_build: None,
+        fields: vec![Field { name: "hash_id".to_owned(), offset: Some(HASH_ID_OFFSET), kind: FieldKind::U32 }],
+        structs: HashMap::new()
+    };
+    let header_end = files.iter().map(header_end).min().unwrap_or(0);
+
+    let mut fields = Vec::new();
+    let bases: Vec<(&SnoReader, u32)> = files.iter().map(|x| (x, 0)).collect();
+    read_columns(&bases, HEADER_START, header_end, "header", 0, &mut fields, &mut layout.structs);
+    layout.fields.extend(fields);
+
+    layout
+}
+
+/// guesses the fields of the words in `start..end` from every base position, adding the structs
+/// the pairs among them point to
+fn read_columns(bases: &[(&SnoReader, u32)], start: u32, end: u32, name: &str, depth: u32, fields: &mut Vec<Field>, structs: &mut HashMap<String, Vec<Field>>) {
+    let mut pos = start;
+    while pos + 4 <= end {
+        if pos + 8 <= end && depth < MAX_DEPTH {
+            if let Some(targets) = pairs_at(bases, pos) {
+                let ref_name = format!("{}_{:x}", name, pos);
+                let (of, stride) = targets.element(&ref_name, depth + 1, structs);
+                fields.push(Field { name: ref_name, offset: Some(pos), kind: FieldKind::Ref { of, stride } });
+                pos += 8;
+                continue;
+            }
+        }
+
+        let values: Vec<u32> = bases.iter().filter_map(|(f, base)| u32_at(f.data(), base + pos)).collect();
+        if let Some(kind) = word_kind(&values) {
+            fields.push(Field { name: format!("{}_{:x}", name, pos), offset: Some(pos), kind });
+        }
+        pos += 4;
+    }
+}
+
+impl<'a> Targets<'a> {
+    /// the element type of the regions: a string, a single word type, or a new struct with its stride
+    fn element(&self, name: &str, depth: u32, structs: &mut HashMap<String, Vec<Field>>) -> (String, Option<u32>) {
+        if self.regions.iter().all(|(f, target, len)| is_string(&f.data()[*target as usize..(target + len) as usize])) {
+            return ("string".to_owned(), None);
+        }
+
+        let stride = self.regions.iter().map(|x| x.2).fold(0, gcd);
+        if stride % 4 != 0 {
+            return ("u8".to_owned(), None);
+        }
+        if stride == 4 {
+            let values: Vec<u32> = self.elements(stride).iter().filter_map(|(f, pos)| u32_at(f.data(), *pos)).collect();
+            let of = match word_kind(&values) {
+                Some(FieldKind::F32) => "f32",
+                _ => "u32"
+            };
+            return (of.to_owned(), None);
+        }
+
+        let mut fields = Vec::new();
+        read_columns(&self.elements(stride), 0, stride, name, depth, &mut fields, structs);
+        structs.insert(name.to_owned(), fields);
+
+        (name.to_owned(), Some(stride))
+    }
+
+    /// start position of every element when the regions are split by `stride`
+    fn elements(&self, stride: u32) -> Vec<(&'a SnoReader, u32)> {
+        self.regions.iter()
+            .flat_map(|(f, target, len)| (0..len/stride).map(move |i| (*f, target + i*stride)))
+            .collect()
+    }
+}
+
+/// the pair at `pos` from every base, if it is a valid pair in enough of them and points somewhere at least once
+fn pairs_at<'a>(bases: &[(&'a SnoReader, u32)], pos: u32) -> Option<Targets<'a>> {
+    let mut regions = Vec::new();
+    let mut fitting = 0;
+    for (f, base) in bases {
+        match pair_at(f, base + pos) {
+            Pair::Valid(target, len) => {
+                regions.push((*f, target, len));
+                fitting += 1;
+            },
+            Pair::Empty => fitting += 1,
+            Pair::Invalid => ()
+        }
+    }
+
+    match !regions.is_empty() && fitting as f32 >= MIN_SHARE * bases.len() as f32 {
+        true => Some(Targets { regions }),
+        false => None
+    }
+}
+
+fn pair_at(f: &SnoReader, pos: u32) -> Pair {
+    let data = f.data();
+    let (offset, len) = match (u32_at(data, pos), u32_at(data, pos + 4)) {
+        (Some(offset), Some(len)) => (offset, len),
+        _ => return Pair::Invalid
+    };
+    if len == 0 {
+        return Pair::Empty;
+    }
+    if offset == 0 { // would point back at the header, usually padding followed by a real offset
+        return Pair::Invalid;
+    }
+
+    let target = match f.ptr(offset) {
+        Some(target) => target as u64,
+        None => return Pair::Invalid
+    };
+    match target >= HEADER_START as u64 && target + len as u64 <= data.len() as u64 {
+        true => Pair::Valid(target as u32, len),
+        false => Pair::Invalid
+    }
+}
+
+/// the header ends where the first region it points to starts
+fn header_end(f: &SnoReader) -> u32 {
+    let mut end = f.meta().len() as u32;
+    let mut pos = HEADER_START;
+    while pos + 8 <= end {
+        if let Pair::Valid(target, _) = pair_at(f, pos) {
+            end = end.min(target.max(pos + 8));
+        }
+        pos += 4;
+    }
+
+    end
+}
+
+/// `None` for words that are always zero, `f32` when every non zero value reads as a plausible float
+fn word_kind(values: &[u32]) -> Option<FieldKind> {
+    let non_zero: Vec<u32> = values.iter().copied().filter(|x| *x != 0).unique().collect();
+    if non_zero.is_empty() {
+        return None;
+    }
+
+    let floats = non_zero.iter().filter(|x| is_float(**x)).count();
+    match floats as f32 >= MIN_SHARE * non_zero.len() as f32 {
+        true => Some(FieldKind::F32),
+        false => Some(FieldKind::U32)
+    }
+}
+
+/// small integers and ids look like denormals or huge numbers, floats found in game data mostly do not
+fn is_float(bits: u32) -> bool {
+    let value = f32::from_bits(bits).abs();
+    bits > 0xFFFF && value.is_finite() && (1e-4..1e7).contains(&value)
+}
+
+/// printable text followed by null padding
+fn is_string(buf: &[u8]) -> bool {
+    let text = match buf.iter().rposition(|x| *x != 0) {
+        Some(last) => &buf[..=last],
+        None => return false
+    };
+
+    match std::str::from_utf8(text) {
+        Ok(text) => text.chars().all(|c| !c.is_control() || c.is_whitespace()),
+        Err(_) => false
+    }
+}
+
+fn u32_at(data: &[u8], pos: u32) -> Option<u32> {
+    data.get(pos as usize..pos as usize + 4).map(|x| u32::from_le_bytes(x.try_into().unwrap()))
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    match b {
+        0 => a,
+        _ => gcd(b, a % b)
+    }
+}
@@ -0,0 +1,333 @@
+use std::borrow::Cow;
+use regex::{Regex, RegexBuilder};
+
+/// characters of a value shown around a match
+const SNIPPET_CHARS: usize = 80;
+/// characters kept in front of the match
+const SNIPPET_CONTEXT: usize = 20;
+
+/// one searchable record of a parsed dataset: a file, or a part of one like a skill tree node
+pub struct Entry<'a> {
+    pub file: &'a str,
+    /// sno ids and other hashes the record can be found by
+    pub hashes: Vec<u32>,
+    pub fields: Vec<(Cow<'a, str>, Cow<'a, str>)>
+}
+
+impl<'a> Entry<'a> {
+    pub fn new(file: &'a str, hashes: Vec<u32>) -> Self {
+        Self {
+            file,
+            hashes,
+            fields: Vec::new()
+        }
+    }
+
+    pub fn field(mut self, key: impl Into<Cow<'a, str>>, value: impl Into<Cow<'a, str>>) -> Self {
+        self.fields.push((key.into(), value.into()));
+        self
+    }
+
+    /// adds a field only when the value is known
+    pub fn field_opt(self, key: &'a str, value: Option<&'a String>) -> Self {
+        match value {
+            Some(value) => self.field(key, value.as_str()),
+            None => self
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Scope {
+    /// the file name, any key, any value or a hash
+    Any,
+    File,
+    Key,
+    Value,
+    Hash
+}
+
+enum Matcher {
+    /// lowercased already when the search ignores case
+    Text(String),
+    Regex(Regex),
+    Hash(u32)
+}
+
+struct Term {
+    scope: Scope,
+    matcher: Matcher
+}
+
+/// a parsed search: whitespace separated terms that all have to match, `"quoted phrases"`, and
+/// `file:`, `key:`, `value:` or `hash:` in front of a term to limit what it is matched against;
+/// `word*` and `word~` are prefix and fuzzy terms for datasets with an index, substrings elsewhere
+#[derive(Default)]
+pub struct Query {
+    terms: Vec<Term>,
+    case_sensitive: bool,
+    text: String,
+    regex: bool,
+    /// the search text when an index can answer it: no regex, no case and no scoped terms
+    index_text: Option<String>
+}
+
+impl Query {
+    /// parses the search bar text, terms are regular expressions when `regex` is set
+    pub fn parse(text: &str, regex: bool, case_sensitive: bool) -> Result<Self, String> {
+        let mut terms = Vec::new();
+        for word in split(text) {
+            let (scope, pattern) = match word.split_once(':') {
+                Some(("file", pattern)) => (Scope::File, pattern),
+                Some(("key", pattern)) => (Scope::Key, pattern),
+                Some(("value", pattern)) => (Scope::Value, pattern),
+                Some(("hash", pattern)) => (Scope::Hash, pattern),
+                _ => (Scope::Any, word.as_str())
+            };
+            let pattern = match regex {
+                true => pattern.trim_matches('"'),
+                false => pattern.trim_matches('"').trim_end_matches(['*', '~'])
+            };
+            if pattern.is_empty() {
+                continue;
+            }
+
+            let matcher = match (scope, regex) {
+                (Scope::Hash, _) => Matcher::Hash(parse_hash(pattern).ok_or_else(|| format!("hash:{} is not a number", pattern))?),
+                (_, true) => Matcher::Regex(RegexBuilder::new(pattern)
+                    .case_insensitive(!case_sensitive)
+                    .build()
+                    .map_err(|x| x.to_string())?),
+                (_, false) => Matcher::Text(match case_sensitive {
+                    true => pattern.to_owned(),
+                    false => pattern.to_lowercase()
+                })
+            };
+            terms.push(Term { scope, matcher });
+        }
+
+        let indexable = !regex && !case_sensitive && !terms.is_empty() && terms.iter().all(|x| x.scope == Scope::Any);
+        let index_text = indexable.then(|| text.trim().to_owned());
+
+        Ok(Self {
+            terms,
+            case_sensitive,
+            text: text.trim().to_owned(),
+            regex,
+            index_text
+        })
+    }
+
+    /// the text and toggles the query was parsed from
+    pub fn source(&self) -> (String, bool, bool) {
+        (self.text.to_owned(), self.regex, self.case_sensitive)
+    }
+
+    pub fn index_text(&self) -> Option<&str> {
+        self.index_text.as_deref()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// whether every term is found in the entry
+    pub fn matches(&self, entry: &Entry) -> bool {
+        self.terms.iter().all(|x| self.term_matches(x, entry))
+    }
+
+    /// whether the file name alone satisfies every term
+    pub fn matches_file(&self, file: &str) -> bool {
+        self.matches(&Entry::new(file, Vec::new()))
+    }
+
+    /// the first field a term is found in, as its index in the entry and a snippet of its value around the match
+    pub fn locate(&self, entry: &Entry) -> Option<(usize, String)> {
+        for (i, (key, value)) in entry.fields.iter().enumerate() {
+            for term in self.terms.iter() {
+                let in_key = matches!(term.scope, Scope::Any | Scope::Key) && self.find(&term.matcher, key).is_some();
+                let in_value = match term.scope {
+                    Scope::Any | Scope::Value => self.find(&term.matcher, value),
+                    _ => None
+                };
+                match (in_value, in_key) {
+                    (Some(offset), _) => return Some((i, snippet(value, offset))),
+                    (None, true) => return Some((i, snippet(value, 0))),
+                    _ => ()
+                }
+            }
+        }
+
+        None
+    }
+
+    fn term_matches(&self, term: &Term, entry: &Entry) -> bool {
+        let text = |x: &str| self.text_matches(&term.matcher, x);
+        let hash = || match &term.matcher {
+            Matcher::Hash(hash) => entry.hashes.contains(hash),
+            Matcher::Text(pattern) => parse_term_hash(pattern).map(|x| entry.hashes.contains(&x)).unwrap_or(false),
+            Matcher::Regex(_) => false
+        };
+
+        match term.scope {
+            Scope::Any => text(entry.file) || entry.fields.iter().any(|(k, v)| text(k) || text(v)) || hash(),
+            Scope::File => text(entry.file),
+            Scope::Key => entry.fields.iter().any(|(k, _)| text(k)),
+            Scope::Value => entry.fields.iter().any(|(_, v)| text(v)),
+            Scope::Hash => hash()
+        }
+    }
+
+    fn text_matches(&self, matcher: &Matcher, text: &str) -> bool {
+        self.find(matcher, text).is_some()
+    }
+
+    /// byte offset of the match in the text, 0 when lowercasing moved the characters around
+    fn find(&self, matcher: &Matcher, text: &str) -> Option<usize> {
+        match matcher {
+            Matcher::Text(pattern) if self.case_sensitive => text.find(pattern.as_str()),
+            Matcher::Text(pattern) => {
+                let lower = text.to_lowercase();
+                lower.find(pattern.as_str()).map(|x| match lower.len() == text.len() && text.is_char_boundary(x) {
+                    true => x,
+                    false => 0
+                })
+            },
+            Matcher::Regex(regex) => regex.find(text).map(|x| x.start()),
+            Matcher::Hash(_) => None
+        }
+    }
+}
+
+/// splits on whitespace outside of double quotes
+pub fn split(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    for c in text.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                word.push(c);
+            },
+            c if c.is_whitespace() && !quoted => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            },
+            c => word.push(c)
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    words
+}
+
+/// one line of the text from a little before the offset, with `…` where it was cut
+fn snippet(text: &str, offset: usize) -> String {
+    let start = text[..offset].char_indices().rev().take(SNIPPET_CONTEXT).last().map(|x| x.0).unwrap_or(offset);
+    let mut snippet: String = text[start..].chars()
+        .take(SNIPPET_CHARS)
+        .map(|x| match x.is_control() {
+            true => ' ',
+            false => x
+        })
+        .collect();
+    if start > 0 {
+        snippet.insert(0, '…');
+    }
+    if text[start..].chars().nth(SNIPPET_CHARS).is_some() {
+        snippet.push('…');
+    }
+
+    snippet
+}
+
+/// a term without a scope is only taken for a hash when it is a decimal number or starts with `0x`,
+/// so words like `bad` or `cafe` are not read as hex
+pub fn parse_term_hash(text: &str) -> Option<u32> {
+    match text.starts_with("0x") || text.starts_with("0X") || text.bytes().all(|x| x.is_ascii_digit()) {
+        true => parse_hash(text),
+        false => None
+    }
+}
+
+/// a decimal number, or hex with or without `0x`
+fn parse_hash(text: &str) -> Option<u32> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok().or_else(|| u32::from_str_radix(text, 16).ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry() -> Entry<'static> {
+        Entry::new("sword_01.stl", vec![0xCAFE, 0xBAD])
+            .field("Name", "Long Sword")
+            .field("Description", "a sword with a long blade")
+    }
+
+    fn matches(text: &str) -> bool {
+        Query::parse(text, false, false).unwrap().matches(&entry())
+    }
+
+    #[test]
+    fn scopes_limit_what_a_term_is_matched_against() {
+        assert!(matches("sword"));
+        assert!(matches("file:sword_01"));
+        assert!(!matches("file:blade"));
+        assert!(matches("key:name"));
+        assert!(!matches("key:long"));
+        assert!(matches("value:blade"));
+        assert!(!matches("value:description"));
+        assert!(matches("file:sword key:description value:blade"));
+        assert!(!matches("sword axe"));
+    }
+
+    #[test]
+    fn quoted_phrases_keep_their_spaces() {
+        assert_eq!(split(r#"value:"long sword" file:x"#), vec![r#"value:"long sword""#, "file:x"]);
+        assert!(matches(r#""long sword""#));
+        assert!(matches(r#"value:"a long blade""#));
+        assert!(!matches(r#""sword long""#));
+        assert!(!matches(r#"key:"long sword""#));
+    }
+
+    #[test]
+    fn invalid_regex_and_hash_are_errors() {
+        assert!(Query::parse("(sword", true, false).is_err());
+        assert!(Query::parse("hash:sword", false, false).is_err());
+
+        let query = Query::parse("^long s.ord$", true, false).unwrap();
+        assert!(query.matches(&entry()));
+        assert!(!Query::parse("^Long", true, true).unwrap().matches(&Entry::new("x", Vec::new()).field("Name", "long")));
+    }
+
+    #[test]
+    fn hashes_are_matched_by_number() {
+        assert!(matches("hash:0xcafe"));
+        assert!(matches("hash:0xCAFE"));
+        assert!(matches("hash:cafe"));
+        assert!(matches("hash:51966"));
+        assert!(!matches("hash:0xbeef"));
+
+        assert!(matches("0xcafe"));
+        assert!(matches("51966"));
+        assert!(matches("2989"));
+        assert!(!matches("cafe"));
+        assert!(!matches("bad"));
+    }
+
+    #[test]
+    fn locate_finds_the_field_and_a_snippet() {
+        let query = Query::parse("blade", false, false).unwrap();
+        assert_eq!(query.locate(&entry()), Some((1, "a sword with a long blade".to_owned())));
+        assert_eq!(Query::parse("key:name", false, false).unwrap().locate(&entry()), Some((0, "Long Sword".to_owned())));
+        assert_eq!(Query::parse("file:sword", false, false).unwrap().locate(&entry()), None);
+    }
+}
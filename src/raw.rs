@@ -0,0 +1,107 @@
+use std::{io::{self, Write}, fs::File, cmp::Reverse, collections::{BTreeMap, BTreeSet}, sync::atomic::{AtomicBool, Ordering}};
+use serde::{Serialize, Deserialize};
+
+/// whether readers keep the bytes the parsers skip, set from `--raw` or the ui
+static RAW_MODE: AtomicBool = AtomicBool::new(false);
+/// most common values listed per unknown field in the report
+const SAMPLE_VALUES: usize = 8;
+
+pub fn set_enabled(enabled: bool) {
+    RAW_MODE.store(enabled, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    RAW_MODE.load(Ordering::Relaxed)
+}
+
+/// bytes a parser skipped or read without knowing their meaning, at an offset from the start of
+/// the struct being read (`header` for the file's own header)
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RawRegion {
+    pub name: String,
+    pub offset: u32,
+    pub hex: String
+}
+
+impl RawRegion {
+    pub fn new(name: &str, offset: u32, bytes: &[u8]) -> Self {
+        Self {
+            name: name.to_owned(),
+            offset,
+            hex: bytes.iter().map(|x| format!("{:02x}", x)).collect()
+        }
+    }
+}
+
+/// values seen for one unknown region while building the report
+#[derive(Default)]
+struct Seen {
+    files: usize,
+    values: BTreeMap<String, usize>
+}
+
+/// how the value of one unknown region is spread over the parsed files
+#[derive(Serialize)]
+pub struct UnknownField {
+    pub name: String,
+    pub offset: u32,
+    pub len: usize,
+    /// files the region appears in
+    pub files: usize,
+    pub occurrences: usize,
+    pub distinct_values: usize,
+    pub varies: bool,
+    pub all_zero: bool,
+    /// most common values with their number of occurrences
+    pub values: Vec<(String, usize)>
+}
+
+/// groups the raw regions of every file by struct, offset and length, varying fields first
+pub fn report<'a>(files: impl Iterator<Item = &'a Vec<RawRegion>>) -> Vec<UnknownField> {
+    let mut fields: BTreeMap<(String, u32, usize), Seen> = BTreeMap::new();
+    for regions in files {
+        let mut seen = BTreeSet::new();
+        for region in regions {
+            let key = (region.name.to_owned(), region.offset, region.hex.len()/2);
+            let field = fields.entry(key.clone()).or_default();
+            if seen.insert(key) {
+                field.files += 1;
+            }
+            *field.values.entry(region.hex.to_owned()).or_default() += 1;
+        }
+    }
+
+    let mut report: Vec<UnknownField> = fields.into_iter()
+        .map(|((name, offset, len), field)| {
+            let mut counts: Vec<(String, usize)> = field.values.into_iter().collect();
+            counts.sort_by_key(|x| Reverse(x.1));
+            UnknownField {
+                name,
+                offset,
+                len,
+                files: field.files,
+                occurrences: counts.iter().map(|x| x.1).sum(),
+                distinct_values: counts.len(),
+                varies: counts.len() > 1,
+                all_zero: counts.iter().all(|x| x.0.chars().all(|c| c == '0')),
+                values: counts.into_iter().take(SAMPLE_VALUES).collect()
+            }
+        })
+        .collect();
+    report.sort_by_key(|x| !x.varies);
+
+    report
+}
+
+/// writes `<extension>_unknown.json` when raw mode is on
+pub fn write_report<'a>(extension: &str, files: impl Iterator<Item = &'a Vec<RawRegion>>) -> io::Result<()> {
+    if !enabled() {
+        return Ok(());
+    }
+
+    let json = serde_json::to_string_pretty(&report(files))?;
+    let mut log = File::create(format!("{}_unknown.json", extension))?;
+    log.write_all(json.as_bytes())?;
+
+    Ok(())
+}
@@ -0,0 +1,164 @@
+use egui::RichText;
+
+use crate::{parsers::{self, Parser, Reveal}, query::{Query, Entry}};
+
+/// hits listed at most, the rest are only counted
+const MAX_HITS: usize = 1000;
+
+/// a record the search found in one of the loaded datasets
+pub struct Hit {
+    /// id of the tab showing the dataset
+    pub tab: usize,
+    pub format: String,
+    pub file: String,
+    /// the field the search was found in, empty when the file name or a hash matched
+    pub key: String,
+    pub value: String,
+    /// every field of the record, to find it again among the records of the file
+    pub fields: Vec<(String, String)>,
+    pub snippet: String
+}
+
+impl Hit {
+    fn new(tab: usize, format: &str, entry: &Entry, query: &Query) -> Self {
+        let (key, value, snippet) = match query.locate(entry) {
+            Some((i, snippet)) => (entry.fields[i].0.to_string(), entry.fields[i].1.to_string(), snippet),
+            None => Default::default()
+        };
+
+        Self {
+            tab,
+            format: format.to_owned(),
+            file: entry.file.to_owned(),
+            key,
+            value,
+            fields: entry.fields.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            snippet
+        }
+    }
+
+    /// what the tab of the dataset is asked to open and scroll to
+    pub fn reveal(&self) -> Reveal {
+        Reveal {
+            file: self.file.to_owned(),
+            key: self.key.to_owned(),
+            value: self.value.to_owned(),
+            fields: self.fields.clone()
+        }
+    }
+}
+
+/// the hits of the search in every loaded dataset at once
+#[derive(Default)]
+pub struct Results {
+    pub open: bool,
+    hits: Vec<Hit>,
+    total: usize,
+    datasets: usize,
+    /// the search version and the tabs the hits were collected from
+    source: Option<(u64, Vec<usize>)>
+}
+
+impl Results {
+    /// searches the datasets again when the search or the open tabs changed since the last time
+    pub fn update(&mut self, query: &Query, version: u64, datasets: Vec<(usize, &dyn Parser)>) {
+        let tabs: Vec<usize> = datasets.iter().map(|x| x.0).collect();
+        if self.source.as_ref() == Some(&(version, tabs.clone())) {
+            return;
+        }
+
+        self.hits.clear();
+        self.total = 0;
+        self.datasets = 0;
+        if !query.is_empty() {
+            let mut found = Vec::new();
+            for (tab, parser) in datasets {
+                let format = parser.tab_title();
+                let entries = parsers::search(parser, query);
+                if !entries.is_empty() {
+                    self.datasets += 1;
+                }
+                found.extend(entries.into_iter().map(|x| (tab, format.to_owned(), x)));
+            }
+            self.total = found.len();
+            // sorted before capping, so the hits shown are the first ones in the list order
+            found.sort_by(|a, b| (&a.1, a.2.file).cmp(&(&b.1, b.2.file)));
+            found.truncate(MAX_HITS);
+            self.hits = found.iter().map(|(tab, format, entry)| Hit::new(*tab, format, entry, query)).collect();
+        }
+        self.source = Some((version, tabs));
+    }
+
+    /// lists the hits, returning the one whose file was clicked
+    pub fn show(&self, ui: &mut egui::Ui, query: &Query) -> Option<&Hit> {
+        ui.horizontal(|h| {
+            h.strong("Results");
+            if query.is_empty() {
+                h.label("type in the search bar to search every loaded dataset");
+            }
+            else {
+                h.label(format!("{} hits in {} datasets", self.total, self.datasets));
+                if self.total > self.hits.len() {
+                    h.weak(format!("showing the first {}", self.hits.len()));
+                }
+            }
+        });
+        ui.separator();
+
+        let mut clicked = None;
+        let row_height = ui.text_style_height(&egui::TextStyle::Body);
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show_rows(ui, row_height, self.hits.len(), |ui, row_range| {
+                egui::Grid::new("results_grid")
+                    .striped(true)
+                    .num_columns(4)
+                    .show(ui, |ui| {
+                        for hit in &self.hits[row_range] {
+                            ui.label(RichText::new(&hit.format).monospace());
+                            if ui.link(&hit.file).on_hover_text("show in its tab").clicked() {
+                                clicked = Some(hit);
+                            }
+                            ui.strong(&hit.key);
+                            ui.label(&hit.snippet);
+                            ui.end_row();
+                        }
+                    });
+            });
+
+        clicked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::parsers::stl::{Stl, StlFile};
+
+    #[test]
+    fn hits_are_sorted_before_they_are_capped() {
+        let mut stl = Stl::new();
+        for i in 0..MAX_HITS + 1 {
+            stl.files.insert(format!("{:04}.stl", i), StlFile {
+                hash_id: i as u32,
+                fields: HashMap::from([("Name".to_owned(), format!("sword {}", i))]),
+                raw: Vec::new()
+            });
+        }
+        let query = Query::parse("sword", false, false).unwrap();
+
+        let mut results = Results::default();
+        results.update(&query, 1, vec![(7, &stl as &dyn Parser)]);
+        assert_eq!(results.total, MAX_HITS + 1);
+        assert_eq!(results.datasets, 1);
+        assert_eq!(results.hits.len(), MAX_HITS);
+        assert!(results.hits.iter().enumerate().all(|(i, x)| x.file == format!("{:04}.stl", i)));
+
+        let reveal = results.hits[3].reveal();
+        assert_eq!(results.hits[3].tab, 7);
+        assert_eq!((reveal.file.as_str(), reveal.key.as_str(), reveal.value.as_str()), ("0003.stl", "Name", "sword 3"));
+        assert!(reveal.is_field("0003.stl", "Name", "sword 3"));
+    }
+}
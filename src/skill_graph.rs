@@ -0,0 +1,197 @@
+use std::{io::{self, Write}, fs::{self, File}, collections::{HashMap, HashSet, VecDeque}, path::Path};
+use itertools::Itertools;
+
+use crate::{utils, parsers::skl::{Skl, SklFile, SklField}, skill_tree::node_name};
+
+const GRAPHS_FOLDER: &str = "skl_graphs";
+/// width the svg fits the tree into, its height follows the tree's proportions
+const SVG_WIDTH: f32 = 1200f32;
+const SVG_MARGIN: f32 = 60f32;
+const ROOT_COLOR: &str = "#dcaa28";
+const NODE_COLOR: &str = "#3c6eb4";
+const GATE_COLOR: &str = "#c83232";
+
+/// writes every file's tree to `skl_graphs/<file>.dot`, `.graphml` and `.svg`
+pub fn write_all(skl: &Skl) -> io::Result<()> {
+    fs::create_dir_all(GRAPHS_FOLDER)?;
+    for (file_name, file) in skl.files.iter() {
+        let stem = Path::new(file_name).file_stem().and_then(|x| x.to_str()).unwrap_or(file_name);
+        let outputs = [("dot", to_dot(file_name, file)), ("graphml", to_graphml(file_name, file)), ("svg", to_svg(file))];
+        for (extension, text) in outputs {
+            let mut f = File::create(Path::new(GRAPHS_FOLDER).join(format!("{}.{}", stem, extension)))?;
+            f.write_all(text.as_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// steps from the nearest root to every node reached through roots and the nodes `passable` lets
+/// through; connections count both ways
+pub fn root_depths(file: &SklFile, passable: impl Fn(u32) -> bool) -> HashMap<u32, u32> {
+    let mut neighbours: HashMap<u32, Vec<u32>> = HashMap::new();
+    for skill in file.skills.iter() {
+        for connection in skill.connections.iter() {
+            neighbours.entry(skill.id).or_default().push(*connection);
+            neighbours.entry(*connection).or_default().push(skill.id);
+        }
+    }
+
+    let roots: HashSet<u32> = file.skills.iter().filter(|x| x.is_root).map(|x| x.id).collect();
+    let mut depths: HashMap<u32, u32> = roots.iter().map(|x| (*x, 0)).collect();
+    let mut queue: VecDeque<u32> = depths.keys().copied().collect();
+    while let Some(id) = queue.pop_front() {
+        let depth = depths[&id];
+        for next in neighbours.get(&id).into_iter().flatten() {
+            if !depths.contains_key(next) && (roots.contains(next) || passable(*next)) {
+                depths.insert(*next, depth + 1);
+                queue.push_back(*next);
+            }
+        }
+    }
+
+    depths
+}
+
+/// label of a node: its reward name, with the points needed when it sits behind a gate
+fn label(skill: &SklField) -> String {
+    match skill.req_points {
+        0 => node_name(skill),
+        points => format!("{}\n{} points", node_name(skill), points)
+    }
+}
+
+/// every connected pair of nodes once, connections are stored on both of their nodes
+fn edges(file: &SklFile) -> Vec<(u32, u32)> {
+    file.skills.iter()
+        .flat_map(|x| x.connections.iter().map(move |connection| (x.id.min(*connection), x.id.max(*connection))))
+        .unique()
+        .sorted()
+        .collect()
+}
+
+/// an undirected graphviz graph with every node pinned to its stored position, for `neato -n`
+pub fn to_dot(file_name: &str, file: &SklFile) -> String {
+    let mut dot = format!("graph \"{}\" {{\n    node [shape=circle, style=filled, fillcolor=\"{}\"];\n", file_name.replace('"', "\\\""), NODE_COLOR);
+    for skill in file.skills.iter() {
+        let mut attributes = vec![
+            format!("label=\"{}\"", label(skill).replace('"', "\\\"").replace('\n', "\\n")),
+            format!("pos=\"{},{}!\"", skill.x, -skill.y)
+        ];
+        if skill.is_root {
+            attributes.push(format!("shape=doublecircle, fillcolor=\"{}\"", ROOT_COLOR));
+        }
+        if skill.req_points > 0 {
+            attributes.push(format!("color=\"{}\", penwidth=2", GATE_COLOR));
+        }
+        dot.push_str(&format!("    {} [{}];\n", skill.id, attributes.join(", ")));
+    }
+    for (a, b) in edges(file) {
+        dot.push_str(&format!("    {} -- {};\n", a, b));
+    }
+    dot.push_str("}\n");
+
+    dot
+}
+
+/// a graphml graph with the node fields as data keys
+pub fn to_graphml(file_name: &str, file: &SklFile) -> String {
+    let keys = [("label", "string"), ("reward_hash", "long"), ("x", "float"), ("y", "float"), ("is_root", "boolean"), ("req_points", "int")];
+    let mut graphml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+    for (name, kind) in keys {
+        graphml.push_str(&format!("  <key id=\"{}\" for=\"node\" attr.name=\"{}\" attr.type=\"{}\"/>\n", name, name, kind));
+    }
+    graphml.push_str(&format!("  <graph id=\"{}\" edgedefault=\"undirected\">\n", utils::xml_escape(file_name)));
+    for skill in file.skills.iter() {
+        graphml.push_str(&format!("    <node id=\"n{}\">\n", skill.id));
+        let values = [
+            utils::xml_escape(&node_name(skill)),
+            skill.reward_hash.to_string(),
+            skill.x.to_string(),
+            skill.y.to_string(),
+            skill.is_root.to_string(),
+            skill.req_points.to_string()
+        ];
+        for ((name, _), value) in keys.iter().zip(values) {
+            graphml.push_str(&format!("      <data key=\"{}\">{}</data>\n", name, value));
+        }
+        graphml.push_str("    </node>\n");
+    }
+    for (a, b) in edges(file) {
+        graphml.push_str(&format!("    <edge source=\"n{}\" target=\"n{}\"/>\n", a, b));
+    }
+    graphml.push_str("  </graph>\n</graphml>\n");
+
+    graphml
+}
+
+/// a standalone drawing of the tree at its stored positions, laid out like the tree view
+pub fn to_svg(file: &SklFile) -> String {
+    let (min_x, max_x) = file.skills.iter().map(|x| x.x).minmax().into_option().unwrap_or((0f32, 0f32));
+    let (min_y, max_y) = file.skills.iter().map(|x| x.y).minmax().into_option().unwrap_or((0f32, 0f32));
+    let scale = (SVG_WIDTH - 2f32*SVG_MARGIN)/(max_x - min_x).max(max_y - min_y).max(1f32);
+    let height = (max_y - min_y)*scale + 2f32*SVG_MARGIN;
+    let positions: HashMap<u32, (f32, f32)> = file.skills.iter()
+        .map(|x| (x.id, ((x.x - min_x)*scale + SVG_MARGIN, (x.y - min_y)*scale + SVG_MARGIN)))
+        .collect();
+
+    let mut svg = format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" font-family=\"sans-serif\" font-size=\"10\">\n", SVG_WIDTH, height, SVG_WIDTH, height);
+    svg.push_str("  <rect width=\"100%\" height=\"100%\" fill=\"#1b1b1b\"/>\n  <g stroke=\"#6e6e6e\">\n");
+    for (a, b) in edges(file) {
+        if let (Some((x1, y1)), Some((x2, y2))) = (positions.get(&a), positions.get(&b)) {
+            svg.push_str(&format!("    <line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\"/>\n", x1, y1, x2, y2));
+        }
+    }
+    svg.push_str("  </g>\n");
+
+    for skill in file.skills.iter() {
+        let (x, y) = positions[&skill.id];
+        let (radius, color) = match skill.is_root {
+            true => (9, ROOT_COLOR),
+            false => (6, NODE_COLOR)
+        };
+        let stroke = match skill.req_points {
+            0 => String::new(),
+            _ => format!(" stroke=\"{}\" stroke-width=\"2\"", GATE_COLOR)
+        };
+        svg.push_str(&format!("  <circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{}\" fill=\"{}\"{}><title>{} ({})</title></circle>\n", x, y, radius, color, stroke, utils::xml_escape(&node_name(skill)), skill.id));
+        for (i, line) in label(skill).lines().enumerate() {
+            svg.push_str(&format!("  <text x=\"{:.1}\" y=\"{:.1}\" fill=\"#dcdcdc\">{}</text>\n", x + radius as f32 + 3f32, y + 3f32 + i as f32*11f32, utils::xml_escape(line)));
+        }
+    }
+    svg.push_str("</svg>\n");
+
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: u32, connections: &[u32]) -> SklField {
+        SklField {
+            id,
+            req_points: 0,
+            reward_hash: 0,
+            is_root: id == 1,
+            x: 0f32,
+            y: 0f32,
+            connections: connections.to_vec(),
+            reward_name: None
+        }
+    }
+
+    #[test]
+    fn edges_are_drawn_once() {
+        let file = SklFile {
+            hash_id: 0,
+            skills: vec![node(1, &[2, 3]), node(2, &[1]), node(3, &[1, 2])],
+            raw: Vec::new()
+        };
+        assert_eq!(edges(&file), [(1, 2), (1, 3), (2, 3)]);
+        let dot = to_dot("test.skl", &file);
+        assert!(dot.starts_with("graph "));
+        assert_eq!(dot.matches(" -- ").count(), 3);
+        assert_eq!(to_graphml("test.skl", &file).matches("<edge ").count(), 3);
+    }
+}
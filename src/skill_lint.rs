@@ -0,0 +1,181 @@
+use std::{io::{self, Write}, fs::File, collections::{HashMap, HashSet}};
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::{skill_graph, parsers::skl::{Skl, SklFile}};
+
+/// nodes closer than this are drawn on top of each other
+const OVERLAP_DISTANCE: f32 = 0.001;
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Check {
+    /// a connection to a node id the file does not have
+    MissingNode,
+    /// a connection the other node does not return
+    OneWayEdge,
+    /// no path from any root leads to the node
+    Unreachable,
+    DuplicateId,
+    OverlappingNodes,
+    /// a node further from the roots needs fewer points than the one leading to it
+    DecreasingGate
+}
+
+/// one problem found in a skill tree, usually bad data or a misaligned parser
+#[derive(Serialize)]
+pub struct Issue {
+    pub file: String,
+    pub check: Check,
+    pub nodes: Vec<u32>,
+    pub message: String
+}
+
+/// checks every file, issues are sorted by file
+pub fn lint(files: &HashMap<String, SklFile>) -> Vec<Issue> {
+    files.keys()
+        .sorted()
+        .flat_map(|file_name| lint_file(file_name, &files[file_name]))
+        .collect()
+}
+
+/// writes the issues of every file to `skl_lint.json`
+pub fn write_report(skl: &Skl) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(&lint(&skl.files))?;
+    let mut log = File::create("skl_lint.json")?;
+    log.write_all(json.as_bytes())?;
+
+    Ok(())
+}
+
+pub fn lint_file(file_name: &str, file: &SklFile) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let mut issue = |check: Check, nodes: Vec<u32>, message: String| {
+        issues.push(Issue { file: file_name.to_owned(), check, nodes, message });
+    };
+
+    for (id, count) in file.skills.iter().map(|x| x.id).counts().into_iter().sorted() {
+        if count > 1 {
+            issue(Check::DuplicateId, vec![id], format!("node id {} is used by {} nodes", id, count));
+        }
+    }
+
+    let edges: HashSet<(u32, u32)> = file.skills.iter()
+        .flat_map(|x| x.connections.iter().map(move |connection| (x.id, *connection)))
+        .collect();
+    let ids: HashSet<u32> = file.skills.iter().map(|x| x.id).collect();
+    // connections count both ways, so one way edges are only reported once
+    for skill in file.skills.iter() {
+        for connection in skill.connections.iter() {
+            if !ids.contains(connection) {
+                issue(Check::MissingNode, vec![skill.id, *connection], format!("node {} connects to missing node {}", skill.id, connection));
+            }
+            else if !edges.contains(&(*connection, skill.id)) {
+                issue(Check::OneWayEdge, vec![skill.id, *connection], format!("node {} connects to {} but not back", skill.id, connection));
+            }
+        }
+    }
+
+    let depths = skill_graph::root_depths(file, |_| true);
+    for skill in file.skills.iter().filter(|x| !depths.contains_key(&x.id)) {
+        issue(Check::Unreachable, vec![skill.id], format!("node {} cannot be reached from a root", skill.id));
+    }
+
+    for (a, b) in file.skills.iter().tuple_combinations() {
+        if a.id != b.id && (a.x - b.x).abs() < OVERLAP_DISTANCE && (a.y - b.y).abs() < OVERLAP_DISTANCE {
+            issue(Check::OverlappingNodes, vec![a.id, b.id], format!("nodes {} and {} are both at ({}, {})", a.id, b.id, a.x, a.y));
+        }
+    }
+
+    let req_points: HashMap<u32, u32> = file.skills.iter().map(|x| (x.id, x.req_points)).collect();
+    for (from, to) in edges.iter().flat_map(|(a, b)| [(a, b), (b, a)]).unique().sorted() {
+        let deeper = match (depths.get(from), depths.get(to)) {
+            (Some(from), Some(to)) => *to == from + 1,
+            _ => false
+        };
+        if let (true, Some(from_points), Some(to_points)) = (deeper, req_points.get(from), req_points.get(to)) {
+            if to_points < from_points {
+                issue(Check::DecreasingGate, vec![*from, *to], format!("node {} needs {} points but leads from node {} needing {}", to, to_points, from, from_points));
+            }
+        }
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::skl::SklField;
+
+    fn node(id: u32, req_points: u32, is_root: bool, x: f32, connections: &[u32]) -> SklField {
+        SklField {
+            id,
+            req_points,
+            reward_hash: 0,
+            is_root,
+            x,
+            y: 0f32,
+            connections: connections.to_vec(),
+            reward_name: None
+        }
+    }
+
+    fn file(skills: Vec<SklField>) -> SklFile {
+        SklFile {
+            hash_id: 0,
+            skills,
+            raw: Vec::new()
+        }
+    }
+
+    fn checks(skills: Vec<SklField>) -> Vec<(Check, Vec<u32>)> {
+        lint_file("tree.skl", &file(skills)).into_iter().map(|x| (x.check, x.nodes)).collect()
+    }
+
+    #[test]
+    fn clean_tree_has_no_issues() {
+        assert!(checks(vec![node(1, 0, true, 0f32, &[2]), node(2, 0, false, 1f32, &[1, 3]), node(3, 2, false, 2f32, &[2])]).is_empty());
+    }
+
+    #[test]
+    fn missing_node() {
+        assert!(checks(vec![node(1, 0, true, 0f32, &[9])]) == [(Check::MissingNode, vec![1, 9])]);
+    }
+
+    #[test]
+    fn one_way_edge() {
+        assert!(checks(vec![node(1, 0, true, 0f32, &[2]), node(2, 0, false, 1f32, &[])]) == [(Check::OneWayEdge, vec![1, 2])]);
+    }
+
+    #[test]
+    fn unreachable() {
+        assert!(checks(vec![node(1, 0, true, 0f32, &[]), node(2, 0, false, 1f32, &[])]) == [(Check::Unreachable, vec![2])]);
+    }
+
+    #[test]
+    fn duplicate_id() {
+        assert!(checks(vec![node(1, 0, true, 0f32, &[]), node(1, 0, true, 1f32, &[])]) == [(Check::DuplicateId, vec![1])]);
+    }
+
+    #[test]
+    fn overlapping_nodes() {
+        assert!(checks(vec![node(1, 0, true, 0f32, &[2]), node(2, 0, false, 0f32, &[1])]) == [(Check::OverlappingNodes, vec![1, 2])]);
+    }
+
+    #[test]
+    fn decreasing_gate() {
+        let skills = vec![node(1, 0, true, 0f32, &[2]), node(2, 5, false, 1f32, &[1, 3]), node(3, 2, false, 2f32, &[2])];
+        assert!(checks(skills) == [(Check::DecreasingGate, vec![2, 3])]);
+    }
+
+    #[test]
+    fn issues_are_sorted_by_file() {
+        let files = HashMap::from([
+            ("b.skl".to_owned(), file(vec![node(1, 0, true, 0f32, &[9])])),
+            ("a.skl".to_owned(), file(vec![node(1, 0, true, 0f32, &[]), node(2, 0, false, 1f32, &[])])),
+            ("c.skl".to_owned(), file(vec![node(1, 0, true, 0f32, &[])]))
+        ]);
+        assert_eq!(lint(&files).iter().map(|x| x.file.as_str()).collect::<Vec<_>>(), ["a.skl", "b.skl"]);
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::{HashMap, HashSet};
+use egui::{Align2, Color32, FontId, Pos2, Rect, Sense, Stroke, Vec2};
+
+use crate::{parsers::skl::{SklFile, SklField}, planner::{Build, MAX_NODE_POINTS}};
+
+const NODE_RADIUS: f32 = 6f32;
+const ROOT_RADIUS: f32 = 9f32;
+/// space kept free around the tree when it is fitted into the view
+const MARGIN: f32 = 30f32;
+const MIN_ZOOM: f32 = 0.1;
+const MAX_ZOOM: f32 = 40f32;
+/// zoom level from which node names are drawn next to the nodes
+const LABEL_ZOOM: f32 = 2f32;
+const NODE_COLOR: Color32 = Color32::from_rgb(60, 110, 180);
+const ROOT_COLOR: Color32 = Color32::from_rgb(220, 170, 40);
+const EDGE_COLOR: Color32 = Color32::from_gray(110);
+const HOVER_COLOR: Color32 = Color32::WHITE;
+const ALLOCATED_COLOR: Color32 = Color32::from_rgb(80, 200, 90);
+const INVALID_COLOR: Color32 = Color32::from_rgb(220, 50, 50);
+const MATCH_COLOR: Color32 = Color32::from_rgb(230, 80, 200);
+
+/// a click on a node: the primary button adds a point, the secondary one removes it
+pub struct NodeClick {
+    pub id: u32,
+    pub add: bool
+}
+
+/// pan and zoom of the skill tree canvas, a zoom of 1 fits the whole tree in the view
+pub struct TreeView {
+    pan: Vec2,
+    zoom: f32
+}
+
+impl Default for TreeView {
+    fn default() -> Self {
+        Self {
+            pan: Vec2::ZERO,
+            zoom: 1f32
+        }
+    }
+}
+
+/// maps the stored node coordinates to the screen
+struct Transform {
+    center: Pos2,
+    tree_center: Pos2,
+    scale: f32
+}
+
+impl Transform {
+    fn new(rect: Rect, skills: &[SklField], view: &TreeView) -> Self {
+        let bounds = Rect::from_points(&skills.iter().map(|x| Pos2::new(x.x, x.y)).collect::<Vec<_>>());
+        let fit = ((rect.width() - 2f32*MARGIN)/bounds.width().max(1f32))
+            .min((rect.height() - 2f32*MARGIN)/bounds.height().max(1f32))
+            .max(f32::EPSILON);
+
+        Self {
+            center: rect.center() + view.pan,
+            tree_center: bounds.center(),
+            scale: fit*view.zoom
+        }
+    }
+
+    fn to_screen(&self, skill: &SklField) -> Pos2 {
+        self.center + (Pos2::new(skill.x, skill.y) - self.tree_center)*self.scale
+    }
+}
+
+impl TreeView {
+    /// draws the nodes of one file at their coordinates with an edge for every connection, the
+    /// points of a build and the nodes found by the search, dragging pans, scrolling or pinching
+    /// zooms and a double click next to the nodes fits the tree again
+    pub fn show(&mut self, ui: &mut egui::Ui, file: &SklFile, build: &Build, invalid: &HashSet<u32>, found: &HashSet<u32>) -> Option<NodeClick> {
+        let (response, painter) = ui.allocate_painter(ui.available_size(), Sense::click_and_drag());
+        let rect = response.rect;
+        painter.rect_filled(rect, 0f32, ui.visuals().extreme_bg_color);
+        if file.skills.is_empty() {
+            painter.text(rect.center(), Align2::CENTER_CENTER, "no nodes", FontId::default(), ui.visuals().text_color());
+            return None;
+        }
+
+        if response.dragged() {
+            self.pan += response.drag_delta();
+        }
+        let pointer = response.hover_pos();
+        if let Some(pointer) = pointer {
+            let (scroll, pinch) = ui.input(|i| (i.scroll_delta.y, i.zoom_delta()));
+            let zoom = (self.zoom*pinch*(scroll/200f32).exp()).clamp(MIN_ZOOM, MAX_ZOOM);
+            if zoom != self.zoom { // keep the point under the pointer in place
+                let from_center = pointer - rect.center();
+                self.pan = from_center - (from_center - self.pan)*(zoom/self.zoom);
+                self.zoom = zoom;
+            }
+        }
+
+        let transform = Transform::new(rect, &file.skills, self);
+        let positions: HashMap<u32, Pos2> = file.skills.iter().map(|x| (x.id, transform.to_screen(x))).collect();
+        let hovered = pointer.and_then(|pointer| {
+            file.skills.iter()
+                .map(|x| (x, positions[&x.id].distance(pointer)))
+                .filter(|x| x.1 <= ROOT_RADIUS)
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|x| x.0)
+        });
+        if response.double_clicked() && hovered.is_none() {
+            *self = Self::default();
+        }
+
+        for skill in file.skills.iter() {
+            for connection in skill.connections.iter() {
+                if let Some(target) = positions.get(connection) {
+                    let highlight = hovered.map(|x| x.id == skill.id || x.id == *connection).unwrap_or(false);
+                    let stroke = match highlight {
+                        true => Stroke::new(2f32, HOVER_COLOR),
+                        false => Stroke::new(1f32, EDGE_COLOR)
+                    };
+                    painter.line_segment([positions[&skill.id], *target], stroke);
+                }
+            }
+        }
+
+        for skill in file.skills.iter() {
+            let pos = positions[&skill.id];
+            let (radius, color) = match skill.is_root {
+                true => (ROOT_RADIUS, ROOT_COLOR),
+                false => (NODE_RADIUS, NODE_COLOR)
+            };
+            let points = build.get(skill.id);
+            let color = match points {
+                0 => color,
+                _ => ALLOCATED_COLOR
+            };
+            painter.circle_filled(pos, radius, color);
+            if found.contains(&skill.id) {
+                painter.circle_stroke(pos, radius + 6f32, Stroke::new(2f32, MATCH_COLOR));
+            }
+            if invalid.contains(&skill.id) {
+                painter.circle_stroke(pos, radius + 2f32, Stroke::new(2f32, INVALID_COLOR));
+            }
+            if hovered.map(|x| x.id == skill.id).unwrap_or(false) {
+                painter.circle_stroke(pos, radius + 4f32, Stroke::new(2f32, HOVER_COLOR));
+            }
+            if points > 0 {
+                painter.text(pos, Align2::CENTER_CENTER, points.to_string(), FontId::monospace(10f32), Color32::BLACK);
+            }
+            if self.zoom >= LABEL_ZOOM {
+                painter.text(pos + Vec2::new(radius + 3f32, 0f32), Align2::LEFT_CENTER, node_name(skill), FontId::proportional(11f32), ui.visuals().text_color());
+            }
+        }
+
+        painter.text(rect.left_bottom() + Vec2::new(6f32, -6f32), Align2::LEFT_BOTTOM, "drag to pan, scroll to zoom, double click to fit, click a node to add a point and right click to remove one", FontId::proportional(11f32), ui.visuals().weak_text_color());
+
+        if let Some(skill) = hovered {
+            egui::show_tooltip_at_pointer(ui.ctx(), ui.id().with("skl_node"), |ui| {
+                ui.strong(node_name(skill));
+                ui.label(format!("id: {}", skill.id));
+                ui.label(format!("reward_hash: {} ({:X})", skill.reward_hash, skill.reward_hash));
+                ui.label(format!("req_points: {}", skill.req_points));
+                ui.label(format!("points: {}/{}", build.get(skill.id), MAX_NODE_POINTS));
+                if skill.is_root {
+                    ui.label("root");
+                }
+                ui.label(format!("connections: {}", skill.connections.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(", ")));
+            });
+        }
+
+        match (hovered, response.clicked(), response.secondary_clicked()) {
+            (Some(skill), true, _) => Some(NodeClick { id: skill.id, add: true }),
+            (Some(skill), _, true) => Some(NodeClick { id: skill.id, add: false }),
+            _ => None
+        }
+    }
+}
+
+/// the resolved power name of a node, or its reward hash
+pub fn node_name(skill: &SklField) -> String {
+    match &skill.reward_name {
+        Some(name) => name.to_owned(),
+        None => format!("{:X}", skill.reward_hash)
+    }
+}
@@ -0,0 +1,199 @@
+use std::{io::{self, Read, Seek, SeekFrom}, fs, path::{Path, PathBuf}};
+
+use crate::raw::{self, RawRegion};
+
+/// offsets in a single file sno are relative to the end of its 0x10 byte header
+const HEADER_SIZE: u32 = 0x10;
+/// folders that hold the payload half of a split sno next to the `meta` folder, casc extraction
+/// writes them there for readers to pair
+pub const PAYLOAD_FOLDERS: [&str; 2] = ["payload", "paylow"];
+
+/// a byte range consumed while tracing, with the struct it belongs to
+#[derive(Clone)]
+pub struct Span {
+    pub start: u32,
+    pub len: u32,
+    pub label: &'static str,
+    /// counts up with every `begin`, so neighbouring records of the same struct can be told apart
+    pub record: usize,
+    pub kind: SpanKind
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SpanKind {
+    Field,
+    Padding,
+    Unknown
+}
+
+/// reads a sno file, together with its payload when the file was split in a meta and a payload file
+///
+/// the payload is mapped right after the meta data, so offsets read from the meta file can be
+/// turned into positions with `ptr` no matter which of the two files they point into
+///
+/// in raw mode every forward relative seek, which is how parsers skip padding, keeps the skipped
+/// bytes so they can be written out with the parsed data
+pub struct SnoReader {
+    data: Vec<u8>,
+    payload_base: Option<u32>,
+    pos: u64,
+    raw: Option<Vec<RawRegion>>,
+    trace: Option<Vec<Span>>,
+    struct_name: &'static str,
+    struct_start: u64,
+    record: usize
+}
+
+impl SnoReader {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let meta = fs::read(path)?;
+        let payload = match SnoReader::payload_path(path) {
+            Some(payload_path) => Some(fs::read(payload_path)?),
+            None => None
+        };
+
+        Ok(SnoReader::new(meta, payload))
+    }
+
+    pub fn new(meta: Vec<u8>, payload: Option<Vec<u8>>) -> Self {
+        let mut data = meta;
+        let payload_base = payload.map(|payload| {
+            let base = (data.len() + 0xF) & !0xF;
+            data.resize(base, 0);
+            data.extend(payload);
+            base as u32
+        });
+
+        Self {
+            data,
+            payload_base,
+            pos: 0,
+            raw: raw::enabled().then(Vec::new),
+            trace: None,
+            struct_name: "header",
+            struct_start: 0,
+            record: 0
+        }
+    }
+
+    /// `.../meta/<group>/<name>` is paired with `.../payload/<group>/<name>`, or `paylow` if there is no payload
+    pub fn payload_path(path: &Path) -> Option<PathBuf> {
+        let components: Vec<_> = path.components().collect();
+        let meta = components.iter().rposition(|x| x.as_os_str() == "meta")?;
+
+        PAYLOAD_FOLDERS.iter()
+            .map(|folder| {
+                let mut payload_path = PathBuf::new();
+                for (i, component) in components.iter().enumerate() {
+                    match i == meta {
+                        true => payload_path.push(folder),
+                        false => payload_path.push(component)
+                    }
+                }
+                payload_path
+            })
+            .find(|x| x.is_file())
+    }
+
+    /// the meta file followed by the payload, as mapped by this reader
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// the part of the data read from the meta file
+    pub fn meta(&self) -> &[u8] {
+        match self.payload_base {
+            Some(base) => &self.data[..base as usize],
+            None => &self.data
+        }
+    }
+
+    /// turns an offset read from the file into a position in this reader, `None` when it does not fit in a u32
+    pub fn ptr(&self, offset: u32) -> Option<u32> {
+        match self.payload_base {
+            Some(base) => base.checked_add(offset),
+            None => offset.checked_add(HEADER_SIZE)
+        }
+    }
+
+    /// starts a new struct at the current position, raw regions are recorded relative to it
+    pub fn begin(&mut self, name: &'static str) {
+        self.struct_name = name;
+        self.struct_start = self.pos;
+        self.record += 1;
+    }
+
+    /// keeps `len` bytes at `pos` as an unknown region when in raw mode
+    pub fn keep_unknown(&mut self, pos: u64, len: usize) {
+        self.keep(pos, len, SpanKind::Unknown);
+    }
+
+    fn keep(&mut self, pos: u64, len: usize, kind: SpanKind) {
+        if let Some(raw) = &mut self.raw {
+            let start = (pos as usize).min(self.data.len());
+            let end = (start + len).min(self.data.len());
+            let offset = pos.saturating_sub(self.struct_start) as u32;
+            raw.push(RawRegion::new(self.struct_name, offset, &self.data[start..end]));
+        }
+        self.trace_span(pos, len, kind);
+    }
+
+    /// records every following read and skip, for the hex viewer
+    pub fn trace(&mut self) {
+        self.trace = Some(Vec::new());
+    }
+
+    pub fn take_trace(&mut self) -> Vec<Span> {
+        self.trace.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    fn trace_span(&mut self, pos: u64, len: usize, kind: SpanKind) {
+        if let (Some(trace), true) = (&mut self.trace, len > 0) {
+            trace.push(Span {
+                start: pos as u32,
+                len: len as u32,
+                label: self.struct_name,
+                record: self.record,
+                kind
+            });
+        }
+    }
+
+    /// the unknown regions kept so far
+    pub fn take_raw(&mut self) -> Vec<RawRegion> {
+        self.raw.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+}
+
+impl Read for SnoReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let start = (self.pos as usize).min(self.data.len());
+        let len = buf.len().min(self.data.len() - start);
+        buf[..len].copy_from_slice(&self.data[start..start + len]);
+        self.trace_span(start as u64, len, SpanKind::Field);
+        self.pos += len as u64;
+
+        Ok(len)
+    }
+}
+
+impl Seek for SnoReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::Current(offset) => {
+                if offset > 0 {
+                    self.keep(self.pos, offset as usize, SpanKind::Padding);
+                }
+                self.pos as i64 + offset
+            },
+            SeekFrom::End(offset) => self.data.len() as i64 + offset
+        };
+        if new_pos < 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "seek before the start of the file"));
+        }
+        self.pos = new_pos as u64;
+
+        Ok(self.pos)
+    }
+}
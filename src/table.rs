@@ -0,0 +1,240 @@
+use std::{cmp::Ordering, collections::HashSet, sync::Arc};
+use egui::{Align2, Color32, FontId, Rect, Sense, Stroke, Vec2};
+
+use crate::query::Query;
+
+const COLUMNS: [Column; 5] = [Column::File, Column::HashId, Column::Hex, Column::Key, Column::Value];
+const DEFAULT_WIDTHS: [f32; 5] = [220f32, 90f32, 80f32, 220f32, 480f32];
+const MIN_WIDTH: f32 = 30f32;
+/// width of the handle right of a column header that is dragged to resize it
+const HANDLE_WIDTH: f32 = 6f32;
+const CELL_PADDING: f32 = 4f32;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Column {
+    File,
+    HashId,
+    Hex,
+    Key,
+    Value
+}
+
+/// one value of a dataset: a string of a string list or an affix value
+pub struct Row {
+    pub file: Arc<str>,
+    pub hash_id: u32,
+    pub key: String,
+    pub value: String
+}
+
+impl Row {
+    fn cell(&self, column: Column) -> String {
+        match column {
+            Column::File => self.file.to_string(),
+            Column::HashId => self.hash_id.to_string(),
+            Column::Hex => format!("{:X}", self.hash_id),
+            Column::Key => self.key.to_owned(),
+            Column::Value => self.value.to_owned()
+        }
+    }
+
+    fn compare(&self, other: &Row, column: Column) -> Ordering {
+        match column {
+            Column::File => self.file.cmp(&other.file),
+            Column::HashId | Column::Hex => self.hash_id.cmp(&other.hash_id),
+            Column::Key => match (self.key.parse::<u64>(), other.key.parse::<u64>()) {
+                (Ok(a), Ok(b)) => a.cmp(&b),
+                _ => self.key.cmp(&other.key)
+            },
+            Column::Value => self.value.cmp(&other.value)
+        }
+    }
+}
+
+/// the rows of a dataset as a table with resizable and sortable columns, only the visible rows are drawn
+pub struct TableView {
+    pub show: bool,
+    widths: [f32; 5],
+    /// column and whether it is ascending, the rows keep their file and key order without one
+    sort: Option<(Column, bool)>,
+    rows: Vec<Row>,
+    /// order of the rows as shown
+    order: Vec<usize>,
+    /// the search the rows were collected for
+    source: Option<(String, bool, bool)>,
+    selected: HashSet<usize>,
+    /// position a shift click selects from
+    anchor: Option<usize>
+}
+
+impl Default for TableView {
+    fn default() -> Self {
+        Self {
+            show: false,
+            widths: DEFAULT_WIDTHS,
+            sort: None,
+            rows: Vec::new(),
+            order: Vec::new(),
+            source: None,
+            selected: HashSet::new(),
+            anchor: None
+        }
+    }
+}
+
+impl TableView {
+    /// whether the rows have to be collected again for the search
+    pub fn is_stale(&self, query: &Query) -> bool {
+        self.source.as_ref() != Some(&query.source())
+    }
+
+    pub fn set_rows(&mut self, query: &Query, rows: Vec<Row>) {
+        self.rows = rows;
+        self.source = Some(query.source());
+        self.selected.clear();
+        self.anchor = None;
+        self.sort_rows();
+    }
+
+    fn sort_rows(&mut self) {
+        let rows = &self.rows;
+        self.anchor = None;
+        self.order = (0..rows.len()).collect();
+        if let Some((column, ascending)) = self.sort {
+            self.order.sort_by(|a, b| match ascending {
+                true => rows[*a].compare(&rows[*b], column),
+                false => rows[*b].compare(&rows[*a], column)
+            });
+        }
+    }
+
+    /// the header with the sort and resize handles, then the visible rows scrolled both ways; a click selects a row, ctrl
+    /// or cmd adds it to the selection and shift selects the rows up to it
+    pub fn show(&mut self, ui: &mut egui::Ui, value_title: &str) {
+        ui.horizontal(|h| {
+            h.label(format!("{} rows, {} selected", self.rows.len(), self.selected.len()));
+            if h.add_enabled(!self.selected.is_empty(), egui::Button::new("copy")).on_hover_text("Copy the selected rows as tab separated text").clicked() {
+                let text = self.selected_text();
+                h.output_mut(|x| x.copied_text = text);
+            }
+        });
+
+        let row_height = ui.text_style_height(&egui::TextStyle::Body) + 2f32*CELL_PADDING;
+        let width: f32 = self.widths.iter().sum();
+        // the header is painted after the rows, following their horizontal scroll
+        let (header, _) = ui.allocate_exact_size(Vec2::new(ui.available_width(), row_height), Sense::hover());
+        let height = (ui.clip_rect().bottom() - ui.cursor().top()).max(row_height);
+        let mut clicked = None;
+        let output = egui::ScrollArea::both()
+            .max_height(height)
+            .auto_shrink([true, false])
+            .show_rows(ui, row_height, self.order.len(), |ui, row_range| {
+                for position in row_range {
+                    let row = &self.rows[self.order[position]];
+                    let (rect, response) = ui.allocate_exact_size(Vec2::new(width, row_height), Sense::click());
+                    let background = match self.selected.contains(&self.order[position]) {
+                        true => ui.visuals().selection.bg_fill,
+                        false if response.hovered() => ui.visuals().widgets.hovered.weak_bg_fill,
+                        false if position % 2 == 1 => ui.visuals().faint_bg_color,
+                        false => Color32::TRANSPARENT
+                    };
+                    ui.painter().rect_filled(rect, 0f32, background);
+                    let mut x = rect.left();
+                    for (i, column) in COLUMNS.iter().enumerate() {
+                        let cell = Rect::from_min_size(egui::pos2(x, rect.top()), Vec2::new(self.widths[i], row_height));
+                        ui.painter().with_clip_rect(cell.shrink(1f32).intersect(ui.clip_rect())).text(cell.left_center() + Vec2::new(CELL_PADDING, 0f32), Align2::LEFT_CENTER, row.cell(*column), FontId::default(), ui.visuals().text_color());
+                        x += self.widths[i];
+                    }
+                    if response.clicked() {
+                        clicked = Some(position);
+                    }
+                }
+            });
+        if self.header(ui, header, output.state.offset.x, value_title) {
+            self.sort_rows();
+        }
+
+        if let Some(position) = clicked {
+            let modifiers = ui.input(|i| i.modifiers);
+            let id = self.order[position];
+            match (modifiers.shift, self.anchor) {
+                (true, Some(anchor)) => {
+                    if !modifiers.command {
+                        self.selected.clear();
+                    }
+                    self.selected.extend(self.order[anchor.min(position)..=anchor.max(position)].iter().copied());
+                },
+                _ if modifiers.command => {
+                    if !self.selected.remove(&id) {
+                        self.selected.insert(id);
+                    }
+                    self.anchor = Some(position);
+                },
+                _ => {
+                    self.selected.clear();
+                    self.selected.insert(id);
+                    self.anchor = Some(position);
+                }
+            }
+        }
+    }
+
+    /// the column titles, a click sorts by the column and dragging the handle right of one resizes
+    /// it; returns whether the sort changed
+    fn header(&mut self, ui: &mut egui::Ui, rect: Rect, offset: f32, value_title: &str) -> bool {
+        let painter = ui.painter().with_clip_rect(rect.intersect(ui.clip_rect()));
+        let mut x = rect.left() - offset;
+        let mut sorted = false;
+        for (i, column) in COLUMNS.iter().enumerate() {
+            let cell = Rect::from_min_size(egui::pos2(x, rect.top()), Vec2::new(self.widths[i], rect.height()));
+            x += self.widths[i];
+            if !cell.intersects(rect) {
+                continue;
+            }
+            let response = ui.interact(cell.intersect(rect), ui.id().with(("table_header", i)), Sense::click());
+            let title = match column {
+                Column::File => "file",
+                Column::HashId => "hash_id",
+                Column::Hex => "hex",
+                Column::Key => "key",
+                Column::Value => value_title
+            };
+            let arrow = match self.sort {
+                Some((sort, true)) if sort == *column => " ⏶",
+                Some((sort, false)) if sort == *column => " ⏷",
+                _ => ""
+            };
+            painter.rect_filled(cell, 0f32, ui.visuals().widgets.inactive.bg_fill);
+            painter.with_clip_rect(cell.shrink(1f32).intersect(rect)).text(cell.left_center() + Vec2::new(CELL_PADDING, 0f32), Align2::LEFT_CENTER, format!("{}{}", title, arrow), FontId::default(), ui.visuals().strong_text_color());
+            if response.clicked() {
+                self.sort = match self.sort {
+                    Some((sort, ascending)) if sort == *column => Some((sort, !ascending)),
+                    _ => Some((*column, true))
+                };
+                sorted = true;
+            }
+
+            let handle = Rect::from_center_size(cell.right_center(), Vec2::new(HANDLE_WIDTH, rect.height())).intersect(rect);
+            let resize = ui.interact(handle, ui.id().with(("table_resize", i)), Sense::drag()).on_hover_cursor(egui::CursorIcon::ResizeHorizontal);
+            if resize.dragged() {
+                self.widths[i] = (self.widths[i] + resize.drag_delta().x).max(MIN_WIDTH);
+            }
+            let stroke = match resize.hovered() || resize.dragged() {
+                true => ui.visuals().widgets.hovered.fg_stroke,
+                false => Stroke::new(1f32, ui.visuals().widgets.noninteractive.bg_stroke.color)
+            };
+            painter.vline(cell.right(), cell.y_range(), stroke);
+        }
+
+        sorted
+    }
+
+    /// the selected rows in the order shown, one line each with the columns separated by tabs
+    fn selected_text(&self) -> String {
+        self.order.iter()
+            .filter(|x| self.selected.contains(x))
+            .map(|x| COLUMNS.iter().map(|column| self.rows[*x].cell(*column)).collect::<Vec<_>>().join("\t"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
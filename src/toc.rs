@@ -0,0 +1,161 @@
+use std::{io, collections::{HashMap, HashSet}};
+use itertools::Itertools;
+use serde::{Serialize, Deserialize};
+
+use crate::utils;
+
+/// sno groups with a parser in this crate, as (group, name, file extension)
+pub const GROUPS: [(u32, &str, &str); 6] = [
+    (25, "Monster", "mon"),
+    (31, "Quest", "qst"),
+    (39, "SkillKit", "skl"),
+    (42, "StringList", "stl"),
+    (99, "Achievement", "ach"),
+    (104, "Affix", "aff"),
+];
+
+/// newer tocs start with a signature before the group count
+const SIGNATURE: u32 = 0xEAF1FE87;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SnoEntry {
+    pub group: u32,
+    pub id: u32,
+    pub name: String
+}
+
+/// the game's sno table of contents (`CoreTOC.dat`), keyed by sno id
+#[derive(Serialize, Deserialize)]
+pub struct CoreToc {
+    pub entries: HashMap<u32, SnoEntry>
+}
+
+impl CoreToc {
+    pub fn read(path: &str) -> io::Result<Self> {
+        let buf = utils::read_file(path)?;
+        CoreToc::parse(&buf)
+    }
+
+    /// loads a previously written `coretoc.json` dump
+    pub fn load(path: &str) -> io::Result<Self> {
+        let buf = utils::read_file(path)?;
+        let toc = serde_json::from_slice(&buf)?;
+
+        Ok(toc)
+    }
+
+    pub fn parse(buf: &[u8]) -> io::Result<Self> {
+        let mut pos = 0usize;
+        let mut num_groups = u32_at(buf, pos)?;
+        if num_groups == SIGNATURE {
+            pos += 4;
+            num_groups = u32_at(buf, pos)?;
+        }
+        pos += 4;
+
+        let num_groups = num_groups as usize;
+        let counts: Vec<u32> = (0..num_groups).map(|i| u32_at(buf, pos + i*4)).collect::<io::Result<_>>()?;
+        let offsets: Vec<u32> = (0..num_groups).map(|i| u32_at(buf, pos + (num_groups + i)*4)).collect::<io::Result<_>>()?;
+        let header_size = pos + num_groups*12 + 4; // counts, offsets, unknown counts and one unknown u32
+
+        let mut entries = HashMap::new();
+        for (count, offset) in counts.iter().zip(offsets.iter()) {
+            let table = header_size + *offset as usize;
+            let names = table + *count as usize * 12;
+            for i in 0..*count as usize {
+                let group = u32_at(buf, table + i*12)?;
+                let id = u32_at(buf, table + i*12 + 4)?;
+                let name_offset = u32_at(buf, table + i*12 + 8)? as usize;
+                let name = c_string_at(buf, names + name_offset)?;
+                entries.insert(id, SnoEntry { group, id, name });
+            }
+        }
+
+        Ok(Self { entries })
+    }
+
+    pub fn get(&self, id: u32) -> Option<&SnoEntry> {
+        self.entries.get(&id)
+    }
+
+    /// matches parsed records (file name, hash_id) to their sno entries, and lists the snos of the
+    /// same groups that were not among them
+    pub fn report(&self, records: &[(String, u32)]) -> TocReport {
+        let mut report = TocReport::default();
+        let mut groups = HashSet::new();
+        let mut parsed = HashSet::new();
+        for (file_name, hash_id) in records.iter().sorted() {
+            parsed.insert(*hash_id);
+            match self.get(*hash_id) {
+                Some(entry) => {
+                    groups.insert(entry.group);
+                    report.records.insert(file_name.to_owned(), TocRecord::new(*hash_id, Some(entry)));
+                },
+                None => {
+                    report.records.insert(file_name.to_owned(), TocRecord::new(*hash_id, None));
+                }
+            }
+        }
+
+        report.unparsed = self.entries.values()
+            .filter(|x| groups.contains(&x.group) && !parsed.contains(&x.id))
+            .sorted_by_key(|x| (x.group, x.id))
+            .cloned()
+            .collect();
+
+        report
+    }
+}
+
+pub fn group_name(group: u32) -> Option<&'static str> {
+    GROUPS.iter().find(|x| x.0 == group).map(|x| x.1)
+}
+
+pub fn group_extension(group: u32) -> Option<&'static str> {
+    GROUPS.iter().find(|x| x.0 == group).map(|x| x.2)
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct TocReport {
+    pub records: HashMap<String, TocRecord>,
+    pub unparsed: Vec<SnoEntry>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TocRecord {
+    pub hash_id: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub group: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub group_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub name: Option<String>
+}
+
+impl TocRecord {
+    fn new(hash_id: u32, entry: Option<&SnoEntry>) -> Self {
+        Self {
+            hash_id,
+            group: entry.map(|x| x.group),
+            group_name: entry.and_then(|x| group_name(x.group)).map(|x| x.to_owned()),
+            name: entry.map(|x| x.name.clone())
+        }
+    }
+}
+
+fn u32_at(buf: &[u8], pos: usize) -> io::Result<u32> {
+    match buf.get(pos..pos + 4) {
+        Some(bytes) => Ok(u32::from_le_bytes(bytes.try_into().unwrap())),
+        None => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "CoreTOC ended early"))
+    }
+}
+
+fn c_string_at(buf: &[u8], pos: usize) -> io::Result<String> {
+    let bytes = buf.get(pos..).ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "CoreTOC name out of range"))?;
+    let end = bytes.iter().position(|x| *x == 0).unwrap_or(bytes.len());
+
+    Ok(String::from_utf8_lossy(&bytes[..end]).into_owned())
+}
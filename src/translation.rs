@@ -0,0 +1,441 @@
+use std::{io::{self, Write}, fs::{self, File}, collections::BTreeSet, path::Path};
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::{utils, parsers::stl::Stl};
+
+/// separates the string list file from the key in a message context, file names cannot contain it
+const CONTEXT_SEPARATOR: char = ':';
+const SOURCE_LANGUAGE: &str = "en-US";
+/// xliff states of a target a translator still has to look at
+const FUZZY_STATES: [&str; 5] = ["needs-review-translation", "needs-review-l10n", "needs-review-adaptation", "needs-l10n", "needs-adaptation"];
+/// xliff states of a target that was not translated yet, whatever text it holds
+const UNTRANSLATED_STATES: [&str; 2] = ["new", "needs-translation"];
+
+/// one string of a translation file
+struct Unit {
+    file: String,
+    key: String,
+    target: String,
+    fuzzy: bool,
+    untranslated: bool
+}
+
+/// what importing a translation file changed, entries are `<file>:<key>`
+#[derive(Serialize, Default)]
+pub struct ImportReport {
+    pub translated: usize,
+    /// strings with an empty translation, or missing from the translation file
+    pub untranslated: Vec<String>,
+    /// strings marked fuzzy or for review, left as they were
+    pub fuzzy: Vec<String>,
+    /// entries whose file or key is not in the string lists
+    pub unknown: Vec<String>
+}
+
+/// writes the string lists to a `.po` file or an `.xlf`/`.xliff` file, depending on the extension
+pub fn export(stl: &Stl, path: &str) -> io::Result<()> {
+    let text = match is_po(path)? {
+        true => write_po(stl),
+        false => write_xliff(stl)
+    };
+    let mut f = File::create(path)?;
+    f.write_all(text.as_bytes())?;
+
+    Ok(())
+}
+
+/// copies the translated strings of a `.po` or `.xliff` file into the string lists, fuzzy and empty
+/// translations are skipped
+pub fn import(stl: &mut Stl, path: &str) -> io::Result<ImportReport> {
+    let text = fs::read_to_string(path)?;
+    let units = match is_po(path)? {
+        true => read_po(&text),
+        false => read_xliff(&text)
+    };
+
+    let mut report = ImportReport::default();
+    let mut seen = BTreeSet::new();
+    for unit in units {
+        let name = format!("{}{}{}", unit.file, CONTEXT_SEPARATOR, unit.key);
+        let value = match stl.files.get_mut(&unit.file).and_then(|x| x.fields.get_mut(&unit.key)) {
+            Some(value) => value,
+            None => {
+                report.unknown.push(name);
+                continue;
+            }
+        };
+        seen.insert(name.to_owned());
+
+        if unit.fuzzy {
+            report.fuzzy.push(name);
+        }
+        else if unit.untranslated || unit.target.is_empty() {
+            report.untranslated.push(name);
+        }
+        else {
+            *value = unit.target;
+            report.translated += 1;
+        }
+    }
+
+    for (file_name, file) in stl.files.iter() {
+        for key in file.fields.keys() {
+            let name = format!("{}{}{}", file_name, CONTEXT_SEPARATOR, key);
+            if !seen.contains(&name) {
+                report.untranslated.push(name);
+            }
+        }
+    }
+    report.untranslated.sort();
+
+    Ok(report)
+}
+
+/// imports a translation file into a json dump, writing the translated dump to `out_path` (by default
+/// `<json>.translated.json` next to it, the input is left alone) and the report to `stl_translation.json`
+pub fn run_import(json_path: &str, path: &str, out_path: Option<&str>) -> io::Result<()> {
+    let mut stl = Stl::load(json_path)?;
+    let report = import(&mut stl, path)?;
+
+    let out_path = match out_path {
+        Some(out_path) => out_path.to_owned(),
+        None => Path::new(json_path).with_extension("translated.json").display().to_string()
+    };
+    let json = serde_json::to_string_pretty(&stl)?;
+    let mut log = File::create(out_path)?;
+    log.write_all(json.as_bytes())?;
+
+    let json = serde_json::to_string_pretty(&report)?;
+    let mut log = File::create("stl_translation.json")?;
+    log.write_all(json.as_bytes())?;
+
+    Ok(())
+}
+
+fn is_po(path: &str) -> io::Result<bool> {
+    match Path::new(path).extension().and_then(|x| x.to_str()) {
+        Some("po") | Some("pot") => Ok(true),
+        Some("xlf") | Some("xliff") => Ok(false),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a .po or .xliff file", path)))
+    }
+}
+
+/// every string sorted by file and key
+fn strings(stl: &Stl) -> impl Iterator<Item = (&String, &String, &String)> {
+    stl.files.iter()
+        .sorted_by_key(|x| x.0)
+        .flat_map(|(file_name, file)| file.fields.iter().sorted_by_key(|x| x.0).map(move |(k, v)| (file_name, k, v)))
+}
+
+fn write_po(stl: &Stl) -> String {
+    let mut po = String::from("msgid \"\"\nmsgstr \"\"\n\"Content-Type: text/plain; charset=UTF-8\\n\"\n");
+    for (file_name, key, value) in strings(stl) {
+        po += &format!("\n#: {}\nmsgctxt {}\nmsgid {}\nmsgstr \"\"\n", file_name, po_quote(&format!("{}{}{}", file_name, CONTEXT_SEPARATOR, key)), po_quote(value));
+    }
+
+    po
+}
+
+fn po_quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted += "\\\"",
+            '\\' => quoted += "\\\\",
+            '\n' => quoted += "\\n",
+            '\r' => quoted += "\\r",
+            '\t' => quoted += "\\t",
+            _ => quoted.push(c)
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+fn po_unquote(text: &str) -> String {
+    let text = text.trim();
+    let text = text.strip_prefix('"').and_then(|x| x.strip_suffix('"')).unwrap_or(text);
+    let mut unquoted = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => unquoted.push('\n'),
+                Some('r') => unquoted.push('\r'),
+                Some('t') => unquoted.push('\t'),
+                Some(c) => unquoted.push(c),
+                None => ()
+            },
+            _ => unquoted.push(c)
+        }
+    }
+
+    unquoted
+}
+
+/// reads the entries with a message context, a string continued on the next lines is joined
+fn read_po(text: &str) -> Vec<Unit> {
+    let mut units = Vec::new();
+    for entry in text.replace("\r\n", "\n").split("\n\n") {
+        let mut fuzzy = false;
+        let (mut context, mut target) = (String::new(), String::new());
+        let mut current = None;
+        for line in entry.lines().map(|x| x.trim()) {
+            if let Some(flags) = line.strip_prefix("#,") {
+                fuzzy |= flags.split(',').any(|x| x.trim() == "fuzzy");
+            }
+            else if line.starts_with('#') || line.is_empty() {
+                continue;
+            }
+            else if let Some(rest) = line.strip_prefix("msgctxt ") {
+                context = po_unquote(rest);
+                current = Some(0);
+            }
+            else if line.starts_with("msgid") {
+                current = None;
+            }
+            else if let Some(rest) = line.strip_prefix("msgstr ").or_else(|| line.strip_prefix("msgstr[0] ")) {
+                target = po_unquote(rest);
+                current = Some(1);
+            }
+            else if line.starts_with('"') {
+                match current {
+                    Some(0) => context += &po_unquote(line),
+                    Some(1) => target += &po_unquote(line),
+                    _ => ()
+                }
+            }
+            else {
+                current = None;
+            }
+        }
+
+        if let Some((file, key)) = context.split_once(CONTEXT_SEPARATOR) {
+            units.push(Unit { file: file.to_owned(), key: key.to_owned(), target, fuzzy, untranslated: false });
+        }
+    }
+
+    units
+}
+
+fn write_xliff(stl: &Stl) -> String {
+    let mut xliff = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<xliff version=\"1.2\" xmlns=\"urn:oasis:names:tc:xliff:document:1.2\">\n");
+    for (file_name, strings) in &strings(stl).group_by(|x| x.0) {
+        xliff += &format!("  <file original=\"{}\" source-language=\"{}\" datatype=\"plaintext\">\n    <body>\n", utils::xml_escape(file_name), SOURCE_LANGUAGE);
+        for (_, key, value) in strings {
+            xliff += &format!("      <trans-unit id=\"{}\" resname=\"{}\">\n", utils::xml_escape(key), utils::xml_escape(key));
+            xliff += &format!("        <source>{}</source>\n", utils::xml_escape(value));
+            xliff += "        <target state=\"needs-translation\"></target>\n";
+            xliff += "      </trans-unit>\n";
+        }
+        xliff += "    </body>\n  </file>\n";
+    }
+    xliff += "</xliff>\n";
+
+    xliff
+}
+
+fn xml_unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        unescaped += &rest[..start];
+        rest = &rest[start..];
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => break
+        };
+        let entity = &rest[1..end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                None => entity.strip_prefix('#').and_then(|x| x.parse().ok()).and_then(char::from_u32)
+            }
+        };
+        match c {
+            Some(c) => {
+                unescaped.push(c);
+                rest = &rest[end + 1..];
+            },
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped += rest;
+
+    unescaped
+}
+
+/// value of an attribute in the text of a start tag
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let start = tag.find(&format!(" {}=\"", name))? + name.len() + 3;
+    let len = tag[start..].find('"')?;
+
+    Some(xml_unescape(&tag[start..start + len]))
+}
+
+/// reads the trans-units of every file, only what is needed for the string lists is understood:
+/// inline tags in a target are dropped and their text kept
+fn read_xliff(text: &str) -> Vec<Unit> {
+    let mut units = Vec::new();
+    let mut file = String::new();
+    let mut unit: Option<Unit> = None;
+    let mut in_target = false;
+
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        if in_target {
+            if let Some(unit) = unit.as_mut() {
+                unit.target += &xml_unescape(&rest[..start]);
+            }
+        }
+        rest = &rest[start..];
+        if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+            let end = cdata.find("]]>").unwrap_or(cdata.len());
+            if let (true, Some(unit)) = (in_target, unit.as_mut()) {
+                unit.target += &cdata[..end];
+            }
+            rest = &cdata[(end + 3).min(cdata.len())..];
+            continue;
+        }
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = &comment[comment.find("-->").map(|x| x + 3).unwrap_or(comment.len())..];
+            continue;
+        }
+
+        let end = match rest.find('>') {
+            Some(end) => end,
+            None => break
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        let name = tag.split(char::is_whitespace).next().unwrap_or_default().trim_end_matches('/');
+
+        match name {
+            "file" => file = attribute(tag, "original").unwrap_or_default(),
+            "trans-unit" => {
+                unit = attribute(tag, "id").map(|key| Unit { file: file.to_owned(), key, target: String::new(), fuzzy: false, untranslated: false });
+            },
+            "target" => {
+                if let Some(unit) = unit.as_mut() {
+                    let state = attribute(tag, "state").unwrap_or_default();
+                    unit.fuzzy = FUZZY_STATES.contains(&state.as_str());
+                    unit.untranslated = UNTRANSLATED_STATES.contains(&state.as_str());
+                }
+                in_target = !tag.ends_with('/');
+            },
+            "/target" => in_target = false,
+            "/trans-unit" => units.extend(unit.take()),
+            _ => ()
+        }
+    }
+
+    units
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::parsers::stl::StlFile;
+
+    const TRICKY: &str = "say \"hi\" \\ back\nthen <b>&amp;</b> & 'more'\ttab";
+
+    fn stl() -> Stl {
+        let mut stl = Stl::new();
+        for (file_name, fields) in [("Items.stl", vec![("Name", TRICKY), ("Desc", "plain")]), ("Odd <&>.stl", vec![("Key \"1\"", "x")])] {
+            stl.files.insert(file_name.to_owned(), StlFile {
+                hash_id: 0,
+                fields: fields.into_iter().map(|(k, v)| (k.to_owned(), v.to_owned())).collect::<HashMap<_, _>>(),
+                raw: Vec::new()
+            });
+        }
+        stl
+    }
+
+    fn names(units: &[Unit]) -> Vec<(String, String)> {
+        units.iter().map(|x| (x.file.to_owned(), x.key.to_owned())).sorted().collect()
+    }
+
+    fn import_text(extension: &str, text: &str) -> (Stl, ImportReport) {
+        let path = std::env::temp_dir().join(format!("translation_test_{}_{}.{}", std::process::id(), extension, extension));
+        fs::write(&path, text).unwrap();
+        let mut stl = stl();
+        let report = import(&mut stl, &path.display().to_string()).unwrap();
+        let _ = fs::remove_file(&path);
+        (stl, report)
+    }
+
+    #[test]
+    fn po_quoting_round_trips() {
+        assert_eq!(po_unquote(&po_quote(TRICKY)), TRICKY);
+        assert_eq!(po_quote("a\"b\\c\n"), "\"a\\\"b\\\\c\\n\"");
+    }
+
+    #[test]
+    fn exports_read_back_as_untranslated_units() {
+        let expected = vec![
+            ("Items.stl".to_owned(), "Desc".to_owned()),
+            ("Items.stl".to_owned(), "Name".to_owned()),
+            ("Odd <&>.stl".to_owned(), "Key \"1\"".to_owned())
+        ];
+
+        let po = read_po(&write_po(&stl()));
+        assert_eq!(names(&po), expected);
+        assert!(po.iter().all(|x| x.target.is_empty() && !x.fuzzy));
+
+        let xliff = read_xliff(&write_xliff(&stl()));
+        assert_eq!(names(&xliff), expected);
+        assert!(xliff.iter().all(|x| x.target.is_empty() && x.untranslated && !x.fuzzy));
+    }
+
+    #[test]
+    fn po_imports_translations_and_reports_the_rest() {
+        let po = format!("{}\n\nmsgctxt \"Items.stl:Name\"\nmsgid \"\"\nmsgstr \"\"\n{}\n\"\\nsecond line\"\n\n#, fuzzy\nmsgctxt \"Items.stl:Desc\"\nmsgid \"plain\"\nmsgstr \"guess\"\n\nmsgctxt \"Gone.stl:Name\"\nmsgid \"x\"\nmsgstr \"y\"\n",
+            "msgid \"\"\nmsgstr \"\"", po_quote(TRICKY));
+        let (stl, report) = import_text("po", &po);
+        assert_eq!(stl.files["Items.stl"].fields["Name"], format!("{}\nsecond line", TRICKY));
+        assert_eq!(stl.files["Items.stl"].fields["Desc"], "plain");
+        assert_eq!(report.translated, 1);
+        assert_eq!(report.fuzzy, ["Items.stl:Desc"]);
+        assert_eq!(report.untranslated, ["Odd <&>.stl:Key \"1\""]);
+        assert_eq!(report.unknown, ["Gone.stl:Name"]);
+    }
+
+    #[test]
+    fn xliff_imports_translations_and_reports_the_rest() {
+        let xliff = r#"<?xml version="1.0"?>
+<xliff version="1.2">
+  <file original="Items.stl">
+    <body>
+      <trans-unit id="Name"><source>x</source><target state="translated">a &quot;b&quot; &lt;c&gt; &amp;amp; <![CDATA[<raw> & ]]>d&#10;e</target></trans-unit>
+      <trans-unit id="Desc"><source>plain</source><target state="needs-review-translation">guess</target></trans-unit>
+    </body>
+  </file>
+  <file original="Odd &lt;&amp;&gt;.stl">
+    <body>
+      <trans-unit id="Key &quot;1&quot;"><source>x</source><target state="needs-translation">x</target></trans-unit>
+    </body>
+  </file>
+  <file original="Gone.stl"><body><trans-unit id="Name"><target>y</target></trans-unit></body></file>
+</xliff>
+"#;
+        let (stl, report) = import_text("xlf", xliff);
+        assert_eq!(stl.files["Items.stl"].fields["Name"], "a \"b\" <c> &amp; <raw> & d\ne");
+        assert_eq!(stl.files["Odd <&>.stl"].fields["Key \"1\""], "x");
+        assert_eq!(report.translated, 1);
+        assert_eq!(report.fuzzy, ["Items.stl:Desc"]);
+        assert_eq!(report.untranslated, ["Odd <&>.stl:Key \"1\""]);
+        assert_eq!(report.unknown, ["Gone.stl:Name"]);
+    }
+}
@@ -1,13 +1,15 @@
-use std::{fs::File, io::{self, Seek, Read}};
+use std::{fs::File, io::{self, Seek, Read}, path::Path};
 
-pub fn padding(f: &mut File, len: i64) -> io::Result<()> {
+use crate::sno::SnoReader;
+
+pub fn padding<R: Seek>(f: &mut R, len: i64) -> io::Result<()> {
     f.seek(io::SeekFrom::Current(len))?;
     Ok(())
 }
 
-pub fn read_offset(f: &mut File, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+pub fn read_offset<R: Read + Seek>(f: &mut R, offset: u64, len: usize) -> io::Result<Vec<u8>> {
     let old_offset = f.stream_position()?;
-    f.seek(io::SeekFrom::Start(offset.into()))?;
+    f.seek(io::SeekFrom::Start(offset))?;
     let mut buf = vec![0u8; len];
     f.read_exact(&mut buf)?;
     f.seek(io::SeekFrom::Start(old_offset))?;
@@ -15,7 +17,7 @@ pub fn read_offset(f: &mut File, offset: u64, len: usize) -> io::Result<Vec<u8>>
     Ok(buf)
 }
 
-pub fn read_u32(f: &mut File) -> io::Result<u32> {
+pub fn read_u32<R: Read>(f: &mut R) -> io::Result<u32> {
     let mut buff = [0; 4];
     f.read_exact(&mut buff)?;
     let result = u32::from_le_bytes(buff);
@@ -23,7 +25,7 @@ pub fn read_u32(f: &mut File) -> io::Result<u32> {
     Ok(result)
 }
 
-pub fn read_f32(f: &mut File) -> io::Result<f32> {
+pub fn read_f32<R: Read>(f: &mut R) -> io::Result<f32> {
     let mut buff = [0; 4];
     f.read_exact(&mut buff)?;
     let result = f32::from_le_bytes(buff);
@@ -31,11 +33,45 @@ pub fn read_f32(f: &mut File) -> io::Result<f32> {
     Ok(result)
 }
 
-pub fn go_to(f: &mut File, offset: u64) -> io::Result<()> {
-    f.seek(io::SeekFrom::Start(offset.into()))?;
+/// reads an offset field and resolves it against the sno's meta/payload layout
+pub fn read_ptr(f: &mut SnoReader) -> io::Result<u32> {
+    let offset = read_u32(f)?;
+
+    f.ptr(offset).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("offset {:#x} is out of range", offset)))
+}
+
+/// position of record `i` of a table of `size` byte records at `offset`, an error when the values read
+/// from the file put it past 4 GB
+pub fn record_pos(offset: u32, i: u32, size: u32) -> io::Result<u64> {
+    i.checked_mul(size)
+        .and_then(|x| x.checked_add(offset))
+        .map(u64::from)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("record {} of {:#x} bytes at {:#x} is out of range", i, size, offset)))
+}
+
+/// reads a u32 whose meaning is not known yet, kept as an unknown region in raw mode
+pub fn read_unknown(f: &mut SnoReader) -> io::Result<u32> {
+    let pos = f.stream_position()?;
+    let value = read_u32(f)?;
+    f.keep_unknown(pos, 4);
+
+    Ok(value)
+}
+
+pub fn go_to<R: Seek>(f: &mut R, offset: u64) -> io::Result<()> {
+    f.seek(io::SeekFrom::Start(offset))?;
     Ok(())
 }
 
+/// escapes text for xml content and attribute values
+pub fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\n', "&#10;")
+}
+
 pub fn read_file(path: &str) -> io::Result<Vec<u8>> {
     let mut f = File::open(path)?;
     let mut buf = Vec::new();
@@ -44,23 +80,18 @@ pub fn read_file(path: &str) -> io::Result<Vec<u8>> {
     Ok(buf)
 }
 
+/// the data file in the working folder, or the one picked instead when it is not there
+pub fn data_file_path(file_name: &str) -> Option<String> {
+    match Path::new(file_name).exists() {
+        true => Some(file_name.to_owned()),
+        false => rfd::FileDialog::new()
+            .add_filter(".json files", &["json"])
+            .set_file_name(file_name)
+            .pick_file()
+            .map(|x| x.display().to_string())
+    }
+}
+
 pub fn load_or_pick_data_file(file_name: &str) -> Option<Vec<u8>> {
-    let buf = match read_file(file_name) {
-        Ok(data) => Some(data),
-        Err(_) => {
-            match rfd::FileDialog::new()
-                .add_filter(".json files", &["json"])
-                .set_file_name(file_name)
-                .pick_file() {
-                    Some(path) => {
-                        let path = path.display().to_string();
-                        let buf = read_file(&path).unwrap();
-                        Some(buf)
-                    },
-                    _ => None
-            }
-        }
-    };
-
-    buf
-}
\ No newline at end of file
+    data_file_path(file_name).map(|x| read_file(&x).unwrap())
+}